@@ -1,13 +1,44 @@
-use inquire::{ InquireError, Password, Text };
+use inquire::validator::Validation;
+use inquire::{ InquireError, Password, Select, Text };
 use serde::{ Deserialize, Serialize };
-use std::error::Error;
+use std::fmt::{ self, Display };
 use std::fs;
+use std::io;
 use uuid::Uuid;
 use zeroize::Zeroize;
-use cli_clipboard;
 
-use crate::cryptography;
-use crate::utils::clear_terminal_screen;
+use crate::backups::rotate_backups;
+use crate::config::Config;
+use crate::cryptography::{ self, CipherSuite, KdfParams };
+use crate::error::VaultError;
+use crate::fields::{ prompt_fields, CustomField };
+use crate::generator::prompt_generated_password;
+use crate::otp::{ prompt_two_factor, Hotp, Totp, TwoFactor };
+use crate::passphrase::prompt_passphrase;
+use crate::session::Session;
+use crate::strength::{ accept_master_password, prompt_with_strength };
+use crate::urls::parse_url;
+use crate::utils::{ clear_terminal_screen, unix_time, wait_for_enter, write_atomic };
+
+/// How the password of an account is entered
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PasswordChoice {
+    Keep,
+    Type,
+    Generate,
+    Passphrase,
+}
+
+impl Display for PasswordChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PasswordChoice::Keep => write!(f, "Keep the current password"),
+            PasswordChoice::Type => write!(f, "Type it"),
+            PasswordChoice::Generate => write!(f, "Generate"),
+            PasswordChoice::Passphrase => write!(f, "Generate a passphrase"),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Zeroize)]
 pub struct Account {
@@ -17,23 +48,268 @@ pub struct Account {
     username: String,
     hints: String,
     password: String,
+    /// Unix time of the last password change. Accounts saved before it was tracked don't have it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password_changed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    totp: Option<Totp>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hotp: Option<Hotp>,
+    /// Sites where this account is used, matched by the find command
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    urls: Vec<String>,
+    /// User defined fields, in the order they are shown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<CustomField>,
+    /// Labels to filter the accounts of a collection by, like "work" or "finance"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// Where the account is shown in the menu, like "work/aws". Accounts without one are shown
+    /// first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    folder: Option<String>,
 }
 
 impl Account {
-    fn new(
+    pub fn new(
         name: String,
         email: String,
         username: String,
         hints: String,
-        password: String
+        password: String,
+        two_factor: TwoFactor
     ) -> Account {
-        Account {
+        let mut account = Account {
             id: Uuid::new_v4().to_string(),
             name,
             email,
             username,
             hints,
             password,
+            password_changed: Some(unix_time()),
+            totp: None,
+            hotp: None,
+            urls: vec![],
+            fields: vec![],
+            tags: vec![],
+            folder: None,
+        };
+        account.set_two_factor(two_factor);
+        account
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn hints(&self) -> &str {
+        &self.hints
+    }
+
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    pub fn password_changed(&self) -> Option<u64> {
+        self.password_changed
+    }
+
+    pub fn totp(&self) -> Option<&Totp> {
+        self.totp.as_ref()
+    }
+
+    pub fn hotp(&self) -> Option<&Hotp> {
+        self.hotp.as_ref()
+    }
+
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Adds url if the account doesn't have it yet
+    pub fn add_url(&mut self, url: String) {
+        if !self.urls.contains(&url) {
+            self.urls.push(url);
+        }
+    }
+
+    /// Returns false if the account doesn't have that URL
+    pub fn remove_url(&mut self, url: &str) -> bool {
+        let len: usize = self.urls.len();
+        self.urls.retain(|u| u != url);
+        self.urls.len() != len
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Tags are compared ignoring case
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim()))
+    }
+
+    /// Adds tag if the account doesn't have it yet. Empty tags are ignored
+    pub fn add_tag(&mut self, tag: &str) {
+        let tag: &str = tag.trim();
+        if !tag.is_empty() && !self.has_tag(tag) {
+            self.tags.push(tag.to_owned());
+        }
+    }
+
+    /// Returns false if the account doesn't have that tag
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let len: usize = self.tags.len();
+        self.tags.retain(|t| !t.eq_ignore_ascii_case(tag.trim()));
+        self.tags.len() != len
+    }
+
+    pub fn folder(&self) -> Option<&str> {
+        self.folder.as_deref()
+    }
+
+    /// Moves the account to folder, or out of any folder if it's empty
+    pub fn set_folder(&mut self, folder: &str) {
+        self.folder = normalize_folder(folder);
+    }
+
+    /// True if the account is in folder or in one of its subfolders
+    pub fn in_folder(&self, folder: &str) -> bool {
+        match (&self.folder, normalize_folder(folder)) {
+            (_, None) => self.folder.is_none(),
+            (None, Some(_)) => false,
+            (Some(own), Some(folder)) => {
+                own == &folder || own.strip_prefix(&folder).is_some_and(|rest| rest.starts_with('/'))
+            }
+        }
+    }
+
+    pub fn fields(&self) -> &[CustomField] {
+        &self.fields
+    }
+
+    pub fn field(&self, name: &str) -> Option<&CustomField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Replaces the field with the same name, or adds it at the end
+    pub fn set_field(&mut self, field: CustomField) {
+        match self.fields.iter_mut().find(|f| f.name == field.name) {
+            Some(current) => {
+                current.zeroize();
+                *current = field;
+            }
+            None => self.fields.push(field),
+        }
+    }
+
+    /// Returns false if the account has no field with that name
+    pub fn remove_field(&mut self, name: &str) -> bool {
+        match self.fields.iter().position(|f| f.name == name) {
+            Some(index) => {
+                self.fields.remove(index).zeroize();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn two_factor(&self) -> TwoFactor {
+        match (&self.totp, &self.hotp) {
+            (Some(totp), _) => TwoFactor::Totp(totp.clone()),
+            (None, Some(hotp)) => TwoFactor::Hotp(hotp.clone()),
+            (None, None) => TwoFactor::None,
+        }
+    }
+
+    /// Replaces the TOTP or HOTP secret, wiping the old one
+    fn set_two_factor(&mut self, two_factor: TwoFactor) {
+        self.totp.zeroize();
+        self.hotp.zeroize();
+        self.totp = None;
+        self.hotp = None;
+        match two_factor {
+            TwoFactor::None => {}
+            TwoFactor::Totp(totp) => {
+                self.totp = Some(totp);
+            }
+            TwoFactor::Hotp(hotp) => {
+                self.hotp = Some(hotp);
+            }
+        }
+    }
+
+    /// The next HOTP code, if the account has an HOTP secret. Its counter is incremented, so the
+    /// accounts must be saved before the code is used
+    pub fn next_hotp_code(&mut self) -> Option<String> {
+        self.hotp.as_mut().map(|hotp| hotp.next_code())
+    }
+
+    /// Raises the HOTP counter to the one of current, if it's the same account and token and its
+    /// counter is higher. Used when restoring a backup, so codes that were already generated
+    /// aren't generated again
+    pub fn keep_hotp_counter(&mut self, current: &Account) {
+        if self.id != current.id {
+            return;
+        }
+        if let (Some(hotp), Some(current)) = (self.hotp.as_mut(), current.hotp.as_ref()) {
+            if hotp.same_token(current) && current.counter() > hotp.counter() {
+                hotp.set_counter(current.counter());
+            }
+        }
+    }
+
+    /// Replaces the password, wiping the old one, and records when it changed
+    fn set_password(&mut self, mut password: String) {
+        if password == self.password {
+            password.zeroize();
+            return;
+        }
+        self.password.zeroize();
+        self.password = password;
+        self.password_changed = Some(unix_time());
+    }
+
+    /// True if key is this account's name or id
+    pub fn matches(&self, key: &str) -> bool {
+        self.name == key || self.id == key
+    }
+
+    /// Replaces the fields that are Some, and keeps the rest
+    pub fn update(
+        &mut self,
+        name: Option<String>,
+        email: Option<String>,
+        username: Option<String>,
+        hints: Option<String>,
+        password: Option<String>,
+        two_factor: Option<TwoFactor>
+    ) {
+        if let Some(name) = name {
+            self.name = name;
+        }
+        if let Some(email) = email {
+            self.email = email;
+        }
+        if let Some(username) = username {
+            self.username = username;
+        }
+        if let Some(hints) = hints {
+            self.hints = hints;
+        }
+        if let Some(password) = password {
+            self.set_password(password);
+        }
+        if let Some(two_factor) = two_factor {
+            self.set_two_factor(two_factor);
         }
     }
 
@@ -43,9 +319,21 @@ impl Account {
         let name: String = Text::new("account name: ").prompt()?;
         let email: String = Text::new("account email: ").prompt()?;
         let username: String = Text::new("account username: ").prompt()?;
+        let urls: Vec<String> = prompt_urls(&[])?;
+        let folder: String = prompt_folder("")?;
+        let tags: String = prompt_tags(&[])?;
         let hints: String = Text::new("password hints: ").prompt()?;
-        let password: String = Text::new("account password: ").prompt()?;
-        Ok(Account::new(name, email, username, hints, password))
+        let password: String = prompt_account_password(None)?;
+        let two_factor: TwoFactor = prompt_two_factor(None)?;
+        let fields: Vec<CustomField> = prompt_fields(&[])?;
+        let mut account: Account = Account::new(name, email, username, hints, password, two_factor);
+        account.urls = urls;
+        account.set_folder(&folder);
+        for tag in tags.split(',') {
+            account.add_tag(tag);
+        }
+        account.fields = fields;
+        Ok(account)
     }
 
     pub fn edit(&mut self) -> Result<(), InquireError> {
@@ -58,16 +346,28 @@ impl Account {
         let username: String = Text::new("account username: ")
             .with_initial_value(&self.username)
             .prompt()?;
+        let urls: Vec<String> = prompt_urls(&self.urls)?;
+        let folder: String = prompt_folder(self.folder().unwrap_or_default())?;
+        let tags: String = prompt_tags(&self.tags)?;
         let hints: String = Text::new("password hints: ").with_initial_value(&self.hints).prompt()?;
-        let password: String = Text::new("account password: ")
-            .with_initial_value(&self.password)
-            .prompt()?;
+        let password: String = prompt_account_password(Some(&self.password))?;
+        let two_factor: TwoFactor = prompt_two_factor(Some(self.two_factor()))?;
+        let fields: Vec<CustomField> = prompt_fields(&self.fields)?;
 
         self.name = name;
         self.email = email;
         self.username = username;
+        self.urls = urls;
+        self.set_folder(&folder);
+        self.tags.clear();
+        for tag in tags.split(',') {
+            self.add_tag(tag);
+        }
         self.hints = hints;
-        self.password = password;
+        self.set_password(password);
+        self.set_two_factor(two_factor);
+        self.fields.zeroize();
+        self.fields = fields;
 
         Ok(())
     }
@@ -77,101 +377,367 @@ impl Account {
         println!("name: \"{}\"", self.name);
         println!("email: \"{}\"", self.email);
         println!("username: \"{}\"", self.username);
+        for url in &self.urls {
+            println!("url: \"{}\"", url);
+        }
+        if let Some(folder) = &self.folder {
+            println!("folder: \"{}\"", folder);
+        }
+        if !self.tags.is_empty() {
+            println!("tags: \"{}\"", self.tags.join(", "));
+        }
         println!("hints: \"{}\"", self.hints);
         if show_pswd {
-            println!("password: \"{}\"\n", self.password);
+            println!("password: \"{}\"", self.password);
         } else {
             println!(
-                "password: \"{}\"\n",
-                String::from_iter(std::iter::repeat("*").take(self.password.len()))
+                "password: \"{}\"",
+                "*".repeat(self.password.len())
             );
         }
+        if let Some(totp) = &self.totp {
+            println!("totp: {}", totp);
+        }
+        if let Some(hotp) = &self.hotp {
+            println!("hotp: counter {}", hotp.counter());
+        }
+        for field in &self.fields {
+            println!("{}: \"{}\"", field.name, field.display_value(show_pswd));
+        }
+        println!();
     }
+}
 
-    pub fn copy_to_clipboard(&self) {
-        cli_clipboard::set_contents(self.password.to_owned()).unwrap();
+/// Asks for the URLs of an account, separated by spaces. current is what it has now
+fn prompt_urls(current: &[String]) -> Result<Vec<String>, InquireError> {
+    let input: String = Text::new("account URLs: ")
+        .with_initial_value(&current.join(" "))
+        .with_help_message("separated by spaces, the find command matches them by domain")
+        .with_validator(|input: &str| {
+            for url in input.split_whitespace() {
+                if let Err(e) = parse_url(url) {
+                    return Ok(Validation::Invalid(format!("{}: {}", url, e).into()));
+                }
+            }
+            Ok(Validation::Valid)
+        })
+        .prompt()?;
+    Ok(input.split_whitespace().map(String::from).collect())
+}
+
+/// Asks for the folder of an account, empty for none
+fn prompt_folder(current: &str) -> Result<String, InquireError> {
+    Text::new("account folder: ")
+        .with_initial_value(current)
+        .with_help_message("like work/aws, or empty for none")
+        .prompt()
+}
+
+/// Asks for the tags of an account, separated by commas. current is what it has now
+fn prompt_tags(current: &[String]) -> Result<String, InquireError> {
+    Text::new("account tags: ")
+        .with_initial_value(&current.join(", "))
+        .with_help_message("separated by commas")
+        .prompt()
+}
+
+/// Removes empty parts and spaces around the slashes of a folder path, so " work//aws/ " is
+/// "work/aws". None if nothing is left
+pub fn normalize_folder(folder: &str) -> Option<String> {
+    let parts: Vec<&str> = folder
+        .split('/')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Asks for an account's password, which can be typed, generated, or a generated passphrase for
+/// passwords that have to be typed by hand. When editing an account, current is its password,
+/// which can also be kept or used as the initial value of the text
+fn prompt_account_password(current: Option<&str>) -> Result<String, InquireError> {
+    let mut choices: Vec<PasswordChoice> = vec![
+        PasswordChoice::Type,
+        PasswordChoice::Generate,
+        PasswordChoice::Passphrase
+    ];
+    if current.is_some() {
+        choices.insert(0, PasswordChoice::Keep);
+    }
+
+    match Select::new("account password: ", choices).prompt()? {
+        PasswordChoice::Keep => Ok(current.unwrap_or_default().to_owned()),
+        PasswordChoice::Type => {
+            prompt_with_strength("account password: ", current.unwrap_or_default(), false)
+        }
+        PasswordChoice::Generate => prompt_generated_password(),
+        PasswordChoice::Passphrase => prompt_passphrase(),
+    }
+}
+
+/// Asks for the master password of a new collection. A generated passphrase is suggested first,
+/// since it's both strong and easy to remember. Weak passwords are refused or need confirmation,
+/// depending on the config
+fn prompt_new_master_password(config: &Config) -> Result<String, InquireError> {
+    let choice: PasswordChoice = Select::new(
+        "This seems to be a new collection. Create a master password: ",
+        vec![PasswordChoice::Passphrase, PasswordChoice::Type]
+    )
+        .with_help_message(
+            "This password will then be used to encrypt and decrypt this collection's accounts file. Keep it somewhere safe and don't lose it"
+        )
+        .prompt()?;
+
+    loop {
+        let mut password: String = match choice {
+            PasswordChoice::Passphrase => prompt_passphrase()?,
+            _ => prompt_with_strength("Master password: ", "", true)?,
+        };
+        if !accept_master_password(&password, config)? {
+            password.zeroize();
+            continue;
+        }
+
+        if choice == PasswordChoice::Type {
+            let mut confirmation: String = Password::new("Confirm master password: ")
+                .without_confirmation()
+                .with_display_mode(inquire::PasswordDisplayMode::Masked)
+                .prompt()?;
+            let matching: bool = confirmation == password;
+            confirmation.zeroize();
+            if !matching {
+                println!("The passwords don't match. Try again");
+                password.zeroize();
+                continue;
+            }
+        }
+        return Ok(password);
     }
 }
 
+pub fn prompt_master_password() -> Result<String, InquireError> {
+    Password::new("Enter master password: ")
+        .without_confirmation()
+        .with_display_mode(inquire::PasswordDisplayMode::Masked)
+        .prompt()
+}
+
+/// Asks for the master password and decrypts the accounts file. Accounts files are self-contained,
+/// legacy_salt is only needed to read the ones created before they had a header. If the file
+/// doesn't exist, a new master password is created and an empty accounts file is saved with
+/// cipher and kdf, after checking its strength against config; any other read error is returned.
+/// The master password is wiped as soon as the key is derived; only the returned session is kept
 pub fn get_accounts(
     file_path: &String,
-    password: &mut String,
-    salt: &[u8; 32]
-) -> Result<Vec<Account>, Box<dyn Error>> {
+    legacy_salt: Option<&[u8; 32]>,
+    cipher: CipherSuite,
+    kdf: &KdfParams,
+    config: &Config
+) -> Result<(Vec<Account>, Session), VaultError> {
     let file_stream: Vec<u8> = match fs::read(file_path) {
         Ok(stream) => stream,
-        Err(_) => {
-            // file_path does not exist
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
             // create file with empty encrypted list
-            *password = Password::new(
-                "This seems to be a new collection. Create a master password: "
-            )
-                .with_help_message(
-                    "This password will then be used to encrypt and decrypt this collection's accounts file. Keep it somewhere safe and don't lose it"
-                )
-                .with_display_mode(inquire::PasswordDisplayMode::Masked)
-                .prompt()?;
-            save_accounts(&vec![], file_path, &password, &salt)?;
-            return Ok(vec![]);
+            let mut password: String = prompt_new_master_password(config)?;
+            let session: Result<Session, VaultError> = Session::new(
+                password.as_bytes(),
+                cipher,
+                kdf
+            );
+            password.zeroize();
+            let session: Session = session?;
+            save_accounts(&vec![], file_path, &session, 0)?;
+            return Ok((vec![], session));
+        }
+        // an accounts file that exists but can't be read must never be replaced with an empty one
+        Err(e) => {
+            return Err(e.into());
         }
     };
 
-    let acc: String;
+    let accounts: Vec<Account>;
+    let session: Session;
 
     loop {
-        let temp_pswd: String = Password::new("Enter master password: ")
-            .without_confirmation()
-            .with_display_mode(inquire::PasswordDisplayMode::Masked)
-            .prompt()?;
-        match
-            cryptography::decrypt(
-                &std::str::from_utf8(&file_stream).unwrap(),
-                temp_pswd.as_bytes(),
-                salt
-            )
-        {
-            Ok(decrypted) => {
-                acc = decrypted;
-                *password = temp_pswd;
+        let mut temp_pswd: String = prompt_master_password()?;
+        let unlocked = open_accounts(&file_stream, temp_pswd.as_bytes(), legacy_salt, cipher);
+        temp_pswd.zeroize();
+        match unlocked {
+            Ok((a, s)) => {
+                accounts = a;
+                session = s;
                 break;
             }
+            Err(VaultError::WrongPassword) => {
+                clear_terminal_screen();
+                println!("Incorrect password! Try again");
+            }
             Err(e) => {
-                match e.downcast_ref::<std::io::Error>() {
-                    Some(er) => {
-                        if er.kind() == std::io::ErrorKind::PermissionDenied {
-                            clear_terminal_screen();
-                            println!("Incorrect password! Try again");
-                        }
-                    }
-                    None => {
-                        return Err(e);
-                    }
-                }
+                return Err(e);
             }
         };
     }
 
-    match serde_json::from_slice(&acc.as_bytes()) {
-        Ok(v) => Ok(v),
-        Err(e) => Err(Box::new(e)),
+    if cryptography::needs_upgrade(&file_stream) {
+        upgrade_accounts_file(&accounts, file_path, &session)?;
+    }
+
+    Ok((accounts, session))
+}
+
+/// Decrypts the contents of an accounts file with its master password, without asking anything.
+/// See get_accounts for legacy_salt and cipher
+pub fn open_accounts(
+    data: &[u8],
+    password: &[u8],
+    legacy_salt: Option<&[u8; 32]>,
+    cipher: CipherSuite
+) -> Result<(Vec<Account>, Session), VaultError> {
+    let (session, decrypted) = Session::unlock(data, password, legacy_salt, cipher)?;
+    Ok((parse_accounts(decrypted)?, session))
+}
+
+/// Decrypts an accounts file again with the session it was unlocked with, without asking for the
+/// master password. Fails with WrongPassword if the file was saved with another key since then
+pub fn reopen_accounts(file_path: &str, session: &Session) -> Result<Vec<Account>, VaultError> {
+    let data: Vec<u8> = fs::read(file_path)?;
+    parse_accounts(session.decrypt(&data)?)
+}
+
+/// Gives the accounts of a backup the HOTP counters of the current accounts where they are
+/// higher, since a counter must never go back. Accounts deleted since the backup can't be checked
+pub fn keep_hotp_counters(restored: &mut [Account], current: &[Account]) {
+    for account in restored {
+        if let Some(current) = current.iter().find(|a| a.id == account.id) {
+            account.keep_hotp_counter(current);
+        }
+    }
+}
+
+/// Decrypts a backup of an accounts file with the master password, to restore it without
+/// unlocking the accounts file first. The current file is only compared with when it can be
+/// decrypted: it must then take the same password, its session is returned so it's saved with
+/// its current settings, and its HOTP counters are kept where they are higher. If it's missing or
+/// corrupt, which is when backups are needed the most, the backup's own session is returned
+pub fn unlock_backup(
+    backup: &[u8],
+    file_path: &str,
+    password: &[u8],
+    legacy_salt: Option<&[u8; 32]>,
+    cipher: CipherSuite
+) -> Result<(Vec<Account>, Session), VaultError> {
+    let (mut accounts, session) = open_accounts(backup, password, legacy_salt, cipher)?;
+    let current: Result<(Vec<Account>, Session), VaultError> = fs
+        ::read(file_path)
+        .map_err(VaultError::from)
+        .and_then(|data| open_accounts(&data, password, legacy_salt, cipher));
+    match current {
+        Ok((mut current, current_session)) => {
+            keep_hotp_counters(&mut accounts, &current);
+            current.zeroize();
+            Ok((accounts, current_session))
+        }
+        // the master password was changed since the backup (or the file was modified), and
+        // restoring it would silently bring the old password back
+        Err(VaultError::WrongPassword) => {
+            accounts.zeroize();
+            Err(VaultError::WrongPassword)
+        }
+        Err(_) => Ok((accounts, session)),
+    }
+}
+
+/// Decrypts a backup of an accounts file. Backups saved before a re-key (or before the file was
+/// upgraded) can't be decrypted with the session's key, so the master password is asked again,
+/// and it must be the current one
+pub fn get_backup_accounts(
+    data: &[u8],
+    session: &Session,
+    legacy_salt: Option<&[u8; 32]>
+) -> Result<Vec<Account>, VaultError> {
+    let decrypted: String = match session.decrypt(data) {
+        Ok(d) => d,
+        Err(VaultError::WrongPassword) => {
+            println!("This backup was saved with different encryption settings");
+            let mut password: String = prompt_master_password()?;
+            let unlocked = session
+                .verify(password.as_bytes())
+                .and_then(|_| {
+                    Session::unlock(data, password.as_bytes(), legacy_salt, CipherSuite::default())
+                });
+            password.zeroize();
+            unlocked?.1
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+    parse_accounts(decrypted)
+}
+
+/// Deserializes decrypted accounts, and wipes the decrypted text
+fn parse_accounts(mut decrypted: String) -> Result<Vec<Account>, VaultError> {
+    let accounts: Result<Vec<Account>, serde_json::Error> = serde_json::from_slice(
+        decrypted.as_bytes()
+    );
+    decrypted.zeroize();
+    Ok(accounts?)
+}
+
+/// Rewrites an accounts file saved in an old format (or with an old cipher) with the current one.
+/// The old file is kept next to it, in case anything goes wrong
+fn upgrade_accounts_file(
+    accounts: &Vec<Account>,
+    file_path: &String,
+    session: &Session
+) -> Result<(), VaultError> {
+    let backup_path: String = format!("{}.pre-upgrade.bak", file_path);
+    write_atomic(&backup_path, &fs::read(file_path)?)?;
+    // the copy above is the backup, so no rolling backup of the old format is made
+    save_accounts(accounts, file_path, session, 0)?;
+
+    clear_terminal_screen();
+    println!("This collection's accounts file was saved in an old format, so it has been upgraded.");
+    println!("A copy of the old file was saved in \"{}\"", backup_path);
+    wait_for_enter();
+    Ok(())
+}
+
+/// Increments the HOTP counter of accounts[index] and saves the accounts right away, before the
+/// code is returned, so a code is never generated twice even if the program doesn't exit cleanly.
+/// No backup is made, or generating a few codes would push every useful backup out. None if the
+/// account has no HOTP secret
+pub fn next_hotp_code(
+    accounts: &mut Vec<Account>,
+    index: usize,
+    file_path: &String,
+    session: &Session
+) -> Result<Option<String>, VaultError> {
+    let code: Option<String> = accounts[index].next_hotp_code();
+    if code.is_some() {
+        save_accounts(accounts, file_path, session, 0)?;
     }
+    Ok(code)
 }
 
+/// Encrypts and saves the accounts. The previous file is kept as a backup first, keeping
+/// up to backup_count of them
 pub fn save_accounts(
     accounts: &Vec<Account>,
     file_path: &String,
-    password: &String,
-    salt: &[u8; 32]
-) -> Result<(), Box<dyn Error>> {
-    let serialized: String = serde_json::to_string(accounts).unwrap();
+    session: &Session,
+    backup_count: usize
+) -> Result<(), VaultError> {
+    let mut serialized: String = serde_json::to_string(accounts)?;
 
-    let encrypted: String = cryptography
-        ::encrypt(serialized.as_bytes(), password.as_bytes(), salt)
-        .unwrap();
+    let encrypted: Result<Vec<u8>, VaultError> = session.encrypt(serialized.as_bytes());
+    serialized.zeroize();
+    let encrypted: Vec<u8> = encrypted?;
 
     let path: std::path::PathBuf = std::path::PathBuf::from(file_path);
     let dir: &std::path::Path = path.parent().unwrap();
     fs::create_dir_all(dir)?;
-    _ = fs::write(file_path, encrypted)?;
+    rotate_backups(file_path, backup_count)?;
+    write_atomic(file_path, &encrypted)?;
     Ok(())
 }
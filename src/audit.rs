@@ -0,0 +1,223 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{ self, Display };
+
+use crate::accounts::Account;
+use crate::breaches::BreachedPasswords;
+use crate::config::Config;
+use crate::error::VaultError;
+use crate::strength::{ estimate, score_label, Strength };
+use crate::utils::unix_time;
+
+/// Passwords scoring below this are reported as weak
+const MIN_SCORE: u8 = 3;
+const DAY: u64 = 24 * 60 * 60;
+
+#[derive(Serialize)]
+pub struct AccountRef {
+    pub id: String,
+    pub name: String,
+}
+
+impl AccountRef {
+    fn new(account: &Account) -> AccountRef {
+        AccountRef { id: account.id().to_owned(), name: account.name.clone() }
+    }
+}
+
+#[derive(Serialize)]
+pub struct WeakPassword {
+    pub account: AccountRef,
+    pub score: u8,
+    pub strength: &'static str,
+    pub crack_time: String,
+}
+
+#[derive(Serialize)]
+pub struct BreachedPassword {
+    pub account: AccountRef,
+    /// How many times the password appears in the breaches
+    pub count: u64,
+}
+
+#[derive(Serialize)]
+pub struct OldPassword {
+    pub account: AccountRef,
+    pub age_days: u64,
+}
+
+/// Problems found in the accounts of a collection
+#[derive(Serialize)]
+pub struct AuditReport {
+    pub accounts: usize,
+    /// Groups of accounts that share the same password
+    pub reused: Vec<Vec<AccountRef>>,
+    pub weak: Vec<WeakPassword>,
+    pub empty: Vec<AccountRef>,
+    pub missing_email: Vec<AccountRef>,
+    pub missing_username: Vec<AccountRef>,
+    /// Passwords older than max_password_age_days. Accounts saved before password changes were
+    /// tracked are never reported
+    pub old: Vec<OldPassword>,
+    /// Whether the passwords were checked against the breached passwords file
+    pub breaches_checked: bool,
+    pub breached: Vec<BreachedPassword>,
+}
+
+impl AuditReport {
+    pub fn issues(&self) -> usize {
+        self.reused.iter().map(|group| group.len()).sum::<usize>() +
+            self.weak.len() +
+            self.empty.len() +
+            self.missing_email.len() +
+            self.missing_username.len() +
+            self.old.len() +
+            self.breached.len()
+    }
+}
+
+/// Fails only if the breached passwords file is set in the config and can't be read
+pub fn audit(accounts: &[Account], config: &Config) -> Result<AuditReport, VaultError> {
+    let mut by_password: HashMap<&str, Vec<&Account>> = HashMap::new();
+    for account in accounts.iter().filter(|a| !a.password().is_empty()) {
+        by_password.entry(account.password()).or_default().push(account);
+    }
+    let mut reused: Vec<Vec<AccountRef>> = by_password
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|group| group.into_iter().map(AccountRef::new).collect())
+        .collect();
+    reused.sort_by(|a, b| a[0].name.cmp(&b[0].name));
+
+    let weak: Vec<WeakPassword> = accounts
+        .iter()
+        .filter(|a| !a.password().is_empty())
+        .filter_map(|a| {
+            let strength: Strength = estimate(a.password());
+            (strength.score < MIN_SCORE).then(|| WeakPassword {
+                account: AccountRef::new(a),
+                score: strength.score,
+                strength: strength.label(),
+                crack_time: strength.crack_time(),
+            })
+        })
+        .collect();
+
+    let now: u64 = unix_time();
+    let old: Vec<OldPassword> = accounts
+        .iter()
+        .filter(|_| config.max_password_age_days > 0)
+        .filter_map(|a| {
+            let age_days: u64 = now.saturating_sub(a.password_changed()?) / DAY;
+            (age_days > config.max_password_age_days).then(|| OldPassword {
+                account: AccountRef::new(a),
+                age_days,
+            })
+        })
+        .collect();
+
+    let mut breached: Vec<BreachedPassword> = Vec::new();
+    if let Some(path) = &config.breached_passwords_file {
+        let mut breaches: BreachedPasswords = BreachedPasswords::open(path)?;
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for account in accounts.iter().filter(|a| !a.password().is_empty()) {
+            let count: u64 = match counts.get(account.password()) {
+                Some(count) => *count,
+                None => {
+                    let count: u64 = breaches.count(account.password())?;
+                    counts.insert(account.password(), count);
+                    count
+                }
+            };
+            if count > 0 {
+                breached.push(BreachedPassword { account: AccountRef::new(account), count });
+            }
+        }
+    }
+
+    let matching = |keep: fn(&Account) -> bool| -> Vec<AccountRef> {
+        accounts
+            .iter()
+            .filter(|a| keep(a))
+            .map(AccountRef::new)
+            .collect()
+    };
+
+    Ok(AuditReport {
+        accounts: accounts.len(),
+        reused,
+        weak,
+        empty: matching(|a| a.password().is_empty()),
+        missing_email: matching(|a| a.email().is_empty()),
+        missing_username: matching(|a| a.username().is_empty()),
+        old,
+        breaches_checked: config.breached_passwords_file.is_some(),
+        breached,
+    })
+}
+
+fn names(accounts: &[AccountRef]) -> String {
+    accounts
+        .iter()
+        .map(|a| format!("\"{}\"", a.name))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Audited {} accounts", self.accounts)?;
+        if !self.breaches_checked {
+            writeln!(f, "(set breached_passwords_file in the config to check for breached passwords)")?;
+        }
+        if self.issues() == 0 {
+            return writeln!(f, "No problems found :)");
+        }
+
+        if !self.breached.is_empty() {
+            writeln!(f, "\nBreached passwords (change these first!):")?;
+            for breached in &self.breached {
+                writeln!(
+                    f,
+                    "  \"{}\": seen {} times in data breaches",
+                    breached.account.name,
+                    breached.count
+                )?;
+            }
+        }
+        if !self.reused.is_empty() {
+            writeln!(f, "\nReused passwords:")?;
+            for group in &self.reused {
+                writeln!(f, "  {}", names(group))?;
+            }
+        }
+        if !self.weak.is_empty() {
+            writeln!(f, "\nWeak passwords (below {}):", score_label(MIN_SCORE))?;
+            for weak in &self.weak {
+                writeln!(
+                    f,
+                    "  \"{}\": {}, could be cracked in {}",
+                    weak.account.name,
+                    weak.strength,
+                    weak.crack_time
+                )?;
+            }
+        }
+        if !self.empty.is_empty() {
+            writeln!(f, "\nEmpty passwords: {}", names(&self.empty))?;
+        }
+        if !self.missing_email.is_empty() {
+            writeln!(f, "\nMissing an email: {}", names(&self.missing_email))?;
+        }
+        if !self.missing_username.is_empty() {
+            writeln!(f, "\nMissing a username: {}", names(&self.missing_username))?;
+        }
+        if !self.old.is_empty() {
+            writeln!(f, "\nPasswords not changed in a while:")?;
+            for old in &self.old {
+                writeln!(f, "  \"{}\": {} days old", old.account.name, old.age_days)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::error::VaultError;
+use crate::utils::write_atomic;
+
+/// One of the encrypted backups of an accounts file. Backups are copies of the file as it was
+/// before each save, named [file_path].1 (the most recent one), [file_path].2, and so on
+pub struct Backup {
+    pub number: usize,
+    pub path: String,
+    pub modified: Option<SystemTime>,
+}
+
+impl fmt::Display for Backup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.modified.and_then(|m| m.elapsed().ok()) {
+            Some(age) => write!(f, "Backup {} (saved {})", self.number, format_age(age.as_secs())),
+            None => write!(f, "Backup {}", self.number),
+        }
+    }
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => String::from("less than a minute ago"),
+        60..=3599 => format!("{} minutes ago", seconds / 60),
+        3600..=86399 => format!("{} hours ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+pub fn backup_path(file_path: &str, number: usize) -> String {
+    format!("{}.{}", file_path, number)
+}
+
+/// Copies the current accounts file to [file_path].1, after shifting the older backups by one
+/// and dropping the ones past count. Nothing is done if the file doesn't exist yet or count is 0
+pub fn rotate_backups(file_path: &str, count: usize) -> Result<(), VaultError> {
+    if count == 0 || !Path::new(file_path).exists() {
+        return Ok(());
+    }
+    // read before anything is moved, so an unreadable file doesn't leave a gap in the numbers
+    let current: Vec<u8> = fs::read(file_path)?;
+
+    let oldest: String = backup_path(file_path, count);
+    if Path::new(&oldest).exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for number in (1..count).rev() {
+        let path: String = backup_path(file_path, number);
+        if Path::new(&path).exists() {
+            fs::rename(&path, backup_path(file_path, number + 1))?;
+        }
+    }
+
+    write_atomic(backup_path(file_path, 1), &current)?;
+    Ok(())
+}
+
+/// Lists the backups of an accounts file, from the most recent one
+pub fn list_backups(file_path: &str) -> Vec<Backup> {
+    let mut backups: Vec<Backup> = vec![];
+    for number in 1.. {
+        let path: String = backup_path(file_path, number);
+        let metadata: fs::Metadata = match fs::metadata(&path) {
+            Ok(m) => m,
+            Err(_) => {
+                break;
+            }
+        };
+        backups.push(Backup { number, path, modified: metadata.modified().ok() });
+    }
+    backups
+}
+
+/// Deletes every backup of an accounts file
+pub fn remove_backups(file_path: &str) {
+    for backup in list_backups(file_path) {
+        _ = fs::remove_file(backup.path);
+    }
+}
@@ -0,0 +1,280 @@
+use sha1::{ Digest, Sha1 };
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{ BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write };
+
+use crate::error::VaultError;
+
+/// First bytes of a binary index built by build_index
+const INDEX_MAGIC: &[u8; 8] = b"PWNDIDX1";
+/// Each entry of a binary index is the raw SHA-1 hash followed by its count (u32, big endian)
+const INDEX_ENTRY_LEN: u64 = 24;
+const HASH_LEN: usize = 20;
+
+/// A local copy of the Have I Been Pwned password list. It's never loaded into memory: every
+/// lookup is a binary search over the file, so it takes a few dozen reads even for the full dump.
+/// Two formats are supported:
+/// - the SHA-1 text file ordered by hash, with one HASH:COUNT line per password
+/// - a binary index with fixed size entries, built from the text file with build_index
+pub struct BreachedPasswords {
+    reader: BufReader<File>,
+    len: u64,
+    binary: bool,
+}
+
+impl BreachedPasswords {
+    pub fn open(path: &str) -> Result<BreachedPasswords, VaultError> {
+        let mut file: File = File::open(path)?;
+        let len: u64 = file.metadata()?.len();
+
+        let mut magic: [u8; 8] = [0; 8];
+        let binary: bool = file.read_exact(&mut magic).is_ok() && &magic == INDEX_MAGIC;
+        if binary && !(len - (INDEX_MAGIC.len() as u64)).is_multiple_of(INDEX_ENTRY_LEN) {
+            return Err(VaultError::CorruptFile("the breached passwords index has a partial entry"));
+        }
+
+        Ok(BreachedPasswords { reader: BufReader::new(file), len, binary })
+    }
+
+    /// How many times password appears in the breaches, 0 if it doesn't
+    pub fn count(&mut self, password: &str) -> Result<u64, VaultError> {
+        let hash: [u8; HASH_LEN] = Sha1::digest(password.as_bytes()).into();
+        if self.binary { self.search_index(&hash) } else { self.search_text(&hash) }
+    }
+
+    fn search_index(&mut self, hash: &[u8; HASH_LEN]) -> Result<u64, VaultError> {
+        let header: u64 = INDEX_MAGIC.len() as u64;
+        let mut low: u64 = 0;
+        let mut high: u64 = (self.len - header) / INDEX_ENTRY_LEN;
+        let mut entry: [u8; INDEX_ENTRY_LEN as usize] = [0; INDEX_ENTRY_LEN as usize];
+
+        while low < high {
+            let middle: u64 = low + (high - low) / 2;
+            self.reader.seek(SeekFrom::Start(header + middle * INDEX_ENTRY_LEN))?;
+            self.reader.read_exact(&mut entry)?;
+            match entry[..HASH_LEN].cmp(hash) {
+                Ordering::Less => {
+                    low = middle + 1;
+                }
+                Ordering::Greater => {
+                    high = middle;
+                }
+                Ordering::Equal => {
+                    let count: [u8; 4] = entry[HASH_LEN..].try_into().unwrap();
+                    return Ok(u32::from_be_bytes(count) as u64);
+                }
+            }
+        }
+        Ok(0)
+    }
+
+    /// Binary search over byte offsets. Each step reads the first line that starts at or after
+    /// the middle offset, so low always points to the start of a line
+    fn search_text(&mut self, hash: &[u8; HASH_LEN]) -> Result<u64, VaultError> {
+        let target: String = hex::encode_upper(hash);
+        let mut low: u64 = 0;
+        let mut high: u64 = self.len;
+        let mut line: Vec<u8> = Vec::new();
+
+        while low < high {
+            let middle: u64 = low + (high - low) / 2;
+            let start: u64 = if middle == 0 {
+                self.reader.seek(SeekFrom::Start(0))?;
+                0
+            } else {
+                // the line before middle is skipped, unless middle is right where a line starts
+                self.reader.seek(SeekFrom::Start(middle - 1))?;
+                line.clear();
+                middle - 1 + (self.reader.read_until(b'\n', &mut line)? as u64)
+            };
+            if start >= high {
+                high = middle;
+                continue;
+            }
+
+            line.clear();
+            let read: u64 = self.reader.read_until(b'\n', &mut line)? as u64;
+            let (line_hash, count) = parse_line(&line)?;
+            match line_hash.to_ascii_uppercase().as_str().cmp(&target) {
+                Ordering::Less => {
+                    low = start + read;
+                }
+                Ordering::Greater => {
+                    high = middle;
+                }
+                Ordering::Equal => {
+                    return Ok(count);
+                }
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// Splits a HASH:COUNT line. The count is optional, some dumps only list the hashes
+fn parse_line(line: &[u8]) -> Result<(String, u64), VaultError> {
+    let line: &str = std::str::from_utf8(line)
+        .map_err(|_| VaultError::CorruptFile("the breached passwords file isn't text"))?
+        .trim_end();
+    let (hash, count) = line.split_once(':').unwrap_or((line, "1"));
+    if hash.len() != HASH_LEN * 2 {
+        return Err(VaultError::CorruptFile("the breached passwords file has an invalid line"));
+    }
+    let count: u64 = count
+        .trim()
+        .parse()
+        .map_err(|_| VaultError::CorruptFile("the breached passwords file has an invalid count"))?;
+    Ok((hash.to_owned(), count))
+}
+
+/// Converts the HIBP text file (ordered by hash) into a binary index, which is about half the
+/// size and faster to search. Returns how many hashes were written
+pub fn build_index(text_path: &str, index_path: &str) -> Result<u64, VaultError> {
+    let reader = BufReader::new(File::open(text_path)?);
+    let mut writer = BufWriter::new(File::create(index_path)?);
+    writer.write_all(INDEX_MAGIC)?;
+
+    let mut previous: Option<[u8; HASH_LEN]> = None;
+    let mut written: u64 = 0;
+    for line in reader.split(b'\n') {
+        let line: Vec<u8> = line?;
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            continue;
+        }
+        let (hash, count) = parse_line(&line)?;
+        let mut raw: [u8; HASH_LEN] = [0; HASH_LEN];
+        hex::decode_to_slice(&hash, &mut raw).map_err(|_|
+            VaultError::CorruptFile("the breached passwords file has an invalid hash")
+        )?;
+        if previous.is_some_and(|p| p >= raw) {
+            return Err(VaultError::CorruptFile("the breached passwords file isn't ordered by hash"));
+        }
+        previous = Some(raw);
+
+        writer.write_all(&raw)?;
+        writer.write_all(&(count.min(u32::MAX as u64) as u32).to_be_bytes())?;
+        written += 1;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    const PASSWORDS: [&str; 8] = [
+        "password",
+        "123456",
+        "qwerty",
+        "letmein",
+        "dragon",
+        "monkey",
+        "iloveyou",
+        "sunshine",
+    ];
+
+    /// A file in the temporary directory, deleted when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(contents: &[u8]) -> TempFile {
+            let path: PathBuf = std::env::temp_dir().join(format!("breaches-{}", Uuid::new_v4()));
+            fs::write(&path, contents).unwrap();
+            TempFile(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// The HASH:COUNT lines of PASSWORDS, ordered by hash, and the passwords in that order. Counts
+    /// have different lengths, so the lines do too
+    fn sorted_lines() -> (Vec<String>, Vec<&'static str>) {
+        let mut lines: Vec<(String, &str)> = PASSWORDS.iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let hash: String = hex::encode_upper(Sha1::digest(p.as_bytes()));
+                (format!("{}:{}", hash, 10u64.pow(i as u32)), *p)
+            })
+            .collect();
+        lines.sort();
+        lines.into_iter().unzip()
+    }
+
+    fn expected_count(password: &str) -> u64 {
+        10u64.pow(PASSWORDS.iter().position(|p| *p == password).unwrap() as u32)
+    }
+
+    /// Looks up every password, and a few that aren't in the file
+    fn assert_counts(path: &str, passwords: &[&str]) {
+        let mut breached: BreachedPasswords = BreachedPasswords::open(path).unwrap();
+        for password in passwords {
+            assert_eq!(breached.count(password).unwrap(), expected_count(password), "{}", password);
+        }
+        for password in ["not breached", "correct horse battery staple", "", "zzzzzz", "000000"] {
+            assert_eq!(breached.count(password).unwrap(), 0, "{}", password);
+        }
+    }
+
+    #[test]
+    fn finds_hashes_on_the_first_and_last_lines() {
+        let (lines, passwords) = sorted_lines();
+        let file: TempFile = TempFile::new((lines.join("\n") + "\n").as_bytes());
+        assert_counts(file.path(), &passwords);
+        let mut breached: BreachedPasswords = BreachedPasswords::open(file.path()).unwrap();
+        let (first, last) = (passwords[0], passwords[passwords.len() - 1]);
+        assert_eq!(breached.count(first).unwrap(), expected_count(first));
+        assert_eq!(breached.count(last).unwrap(), expected_count(last));
+    }
+
+    #[test]
+    fn reads_crlf_line_endings() {
+        let (lines, passwords) = sorted_lines();
+        let file: TempFile = TempFile::new((lines.join("\r\n") + "\r\n").as_bytes());
+        assert_counts(file.path(), &passwords);
+    }
+
+    #[test]
+    fn reads_files_without_a_final_newline() {
+        let (lines, passwords) = sorted_lines();
+        for end in 1..=lines.len() {
+            let file: TempFile = TempFile::new(lines[..end].join("\n").as_bytes());
+            assert_counts(file.path(), &passwords[..end]);
+        }
+    }
+
+    #[test]
+    fn an_empty_file_has_no_breached_passwords() {
+        let file: TempFile = TempFile::new(b"");
+        assert_counts(file.path(), &[]);
+    }
+
+    #[test]
+    fn the_index_gives_the_same_counts() {
+        let (lines, passwords) = sorted_lines();
+        let text: TempFile = TempFile::new((lines.join("\n") + "\n").as_bytes());
+        let index: TempFile = TempFile::new(b"");
+        assert_eq!(build_index(text.path(), index.path()).unwrap(), PASSWORDS.len() as u64);
+        assert_counts(index.path(), &passwords);
+    }
+
+    #[test]
+    fn building_an_index_from_an_unsorted_file_fails() {
+        let (mut lines, _) = sorted_lines();
+        lines.swap(2, 5);
+        let text: TempFile = TempFile::new(lines.join("\n").as_bytes());
+        let index: TempFile = TempFile::new(b"");
+        assert!(matches!(build_index(text.path(), index.path()), Err(VaultError::CorruptFile(_))));
+    }
+}
@@ -0,0 +1,862 @@
+use clap::{ Args, Parser, Subcommand, ValueEnum };
+use inquire::Password;
+use std::error::Error;
+use std::fs;
+use std::io::{ self, BufRead, BufReader, Read };
+use std::time::Duration;
+use url::Url;
+use zeroize::Zeroize;
+
+use crate::accounts::*;
+use crate::audit::{ audit, AuditReport };
+use crate::backups::*;
+use crate::breaches::build_index;
+use crate::collections::*;
+use crate::config::*;
+use crate::cryptography::{ CipherSuite, KdfAlgorithm, KdfParams };
+use crate::error::{ OtpError, UrlError, VaultError };
+use crate::fields::CustomField;
+use crate::generator::{ generate, CharClass, PasswordOptions };
+use crate::manager::{ COLLECTIONS_FILE_PATH, CONFIG_FILE_PATH };
+use crate::otp::{ Hotp, Totp, TwoFactor };
+use crate::passphrase::{ generate_passphrase, PassphraseOptions };
+use crate::search::search;
+use crate::session::Session;
+use crate::strength::{ estimate, Strength };
+use crate::urls::{ parse_url, url_match, UrlMatch };
+
+/// Simple CLI password manager. Run it without a command to use the interactive menu
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub password: PasswordSource,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Where the master password is read from. If none of these is given, it is asked in the terminal
+#[derive(Args)]
+pub struct PasswordSource {
+    /// Read the master password from the first line of stdin
+    #[arg(long, global = true)]
+    password_stdin: bool,
+
+    /// Read the master password from an environment variable
+    #[arg(long, global = true, value_name = "VAR")]
+    password_env: Option<String>,
+
+    /// Read the master password from the first line of an open file descriptor (Unix only)
+    #[arg(long, global = true, value_name = "FD")]
+    password_fd: Option<i32>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List, add and remove collections
+    Collections {
+        #[command(subcommand)]
+        action: CollectionsCommand,
+    },
+    /// List, show, add, edit and remove the accounts of a collection
+    Accounts {
+        // boxed, since adding or editing an account takes many more arguments than any other command
+        #[command(subcommand)]
+        action: Box<AccountsCommand>,
+    },
+    /// Print a single field of an account
+    Get {
+        collection: String,
+        /// Account name or id
+        account: String,
+        #[arg(long, value_enum, default_value_t = Field::Password)]
+        field: Field,
+        /// Print the custom field with this name instead
+        #[arg(long, value_name = "NAME", conflicts_with = "field")]
+        custom: Option<String>,
+    },
+    /// Print the names of the accounts that fuzzy match a query, best matches first. The name,
+    /// username, email, URLs, tags, folder, hints and custom fields that aren't hidden are
+    /// searched
+    Search {
+        collection: String,
+        /// Every word must match somewhere in the account
+        query: String,
+        /// Also print where each account matched
+        #[arg(long)]
+        verbose: bool,
+        #[command(flatten)]
+        filter: AccountFilter,
+    },
+    /// Print the names of the accounts used on a site, best matches first
+    Find {
+        collection: String,
+        #[arg(long)]
+        url: String,
+        /// How loosely the account URLs are matched: same registrable domain (like example.co.uk
+        /// for login.example.co.uk), same host, or the URL starts with the account URL
+        #[arg(long = "match", value_enum, default_value_t = MatchArg::Domain)]
+        match_by: MatchArg,
+        #[command(flatten)]
+        filter: AccountFilter,
+    },
+    /// Print the next HOTP code of an account, and save its incremented counter
+    Hotp {
+        collection: String,
+        /// Account name or id
+        account: String,
+    },
+    /// Report breached, reused, weak, empty and old passwords, and accounts missing an email or
+    /// username, as JSON
+    Audit {
+        collection: String,
+    },
+    /// Build a binary index from the Have I Been Pwned SHA-1 text file (ordered by hash), to use
+    /// as breached_passwords_file in the config
+    BreachIndex {
+        /// The downloaded text file, with one HASH:COUNT line per password
+        input: String,
+        output: String,
+    },
+    /// List and restore the backups of a collection's accounts file
+    Backups {
+        #[command(subcommand)]
+        action: BackupsCommand,
+    },
+    /// Print a random password
+    Generate {
+        #[arg(long, default_value_t = PasswordOptions::default().length)]
+        length: usize,
+        #[arg(long)]
+        no_lowercase: bool,
+        #[arg(long)]
+        no_uppercase: bool,
+        #[arg(long)]
+        no_digits: bool,
+        #[arg(long)]
+        no_symbols: bool,
+        /// Leave out characters that are easy to confuse, like 0 and O or 1 and l
+        #[arg(long)]
+        exclude_ambiguous: bool,
+    },
+    /// Print a random passphrase made of words, and its entropy
+    Passphrase {
+        #[arg(long, default_value_t = PassphraseOptions::default().words)]
+        words: usize,
+        #[arg(long, default_value_t = PassphraseOptions::default().separator)]
+        separator: String,
+        /// Make the first letter of every word uppercase
+        #[arg(long)]
+        capitalize: bool,
+        /// Append a random digit to one of the words
+        #[arg(long)]
+        add_digit: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CollectionsCommand {
+    List,
+    Add {
+        name: String,
+        /// Where the collection's accounts will be saved
+        file_path: String,
+        #[arg(long, value_enum, default_value_t = CipherArg::Xchacha20Poly1305)]
+        cipher: CipherArg,
+        /// Use Argon2 parameters that take around one second on this machine
+        #[arg(long, conflicts_with_all = ["memory", "iterations", "parallelism"])]
+        calibrate: bool,
+        /// Argon2 memory, in MiB (at most 4096)
+        #[arg(long)]
+        memory: Option<u32>,
+        /// Argon2 iterations (at most 1000)
+        #[arg(long)]
+        iterations: Option<u32>,
+        /// Argon2 parallelism (at most 64)
+        #[arg(long)]
+        parallelism: Option<u32>,
+    },
+    Rm {
+        name: String,
+        /// Don't delete the accounts file and its backups
+        #[arg(long)]
+        keep_file: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AccountsCommand {
+    List {
+        collection: String,
+        #[command(flatten)]
+        filter: AccountFilter,
+    },
+    Show {
+        collection: String,
+        /// Account name or id
+        account: String,
+        #[arg(long)]
+        show_password: bool,
+    },
+    /// Add an account. If --password is not given, it is asked in the terminal
+    Add {
+        collection: String,
+        name: String,
+        #[arg(long, default_value = "")]
+        email: String,
+        #[arg(long, default_value = "")]
+        username: String,
+        #[arg(long, default_value = "")]
+        hints: String,
+        /// The account's password. Beware that it may end up in your shell history
+        #[arg(long)]
+        password: Option<String>,
+        #[command(flatten)]
+        two_factor: TwoFactorArgs,
+        /// A site where the account is used. Can be repeated
+        #[arg(long = "url", value_name = "URL", value_parser = parse_url_arg)]
+        urls: Vec<String>,
+        /// A custom field, as NAME=VALUE or NAME:TYPE=VALUE (text, hidden, url, number or date).
+        /// Can be repeated
+        #[arg(long = "custom", value_name = "FIELD", value_parser = CustomField::parse)]
+        custom: Vec<CustomField>,
+        /// A tag, or several separated by commas. Can be repeated
+        #[arg(long = "tag", value_name = "TAG", value_delimiter = ',')]
+        tags: Vec<String>,
+        /// The folder the account is in, like work/aws
+        #[arg(long)]
+        folder: Option<String>,
+    },
+    /// Edit an account. Only the given fields are changed
+    Edit {
+        collection: String,
+        /// Account name or id
+        account: String,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        email: Option<String>,
+        #[arg(long)]
+        username: Option<String>,
+        #[arg(long)]
+        hints: Option<String>,
+        /// The account's new password. Beware that it may end up in your shell history
+        #[arg(long)]
+        password: Option<String>,
+        #[command(flatten)]
+        two_factor: TwoFactorArgs,
+        /// Remove the account's TOTP or HOTP secret
+        #[arg(long, conflicts_with_all = ["totp", "hotp"])]
+        remove_two_factor: bool,
+        /// Add a site where the account is used. Can be repeated
+        #[arg(long = "url", value_name = "URL", value_parser = parse_url_arg)]
+        urls: Vec<String>,
+        /// Remove one of the account's URLs. Can be repeated
+        #[arg(long = "remove-url", value_name = "URL")]
+        remove_urls: Vec<String>,
+        /// Add a custom field, or replace the one with the same name, as NAME=VALUE or
+        /// NAME:TYPE=VALUE (text, hidden, url, number or date). Can be repeated
+        #[arg(long = "custom", value_name = "FIELD", value_parser = CustomField::parse)]
+        custom: Vec<CustomField>,
+        /// Remove the custom field with this name. Can be repeated
+        #[arg(long, value_name = "NAME")]
+        remove_custom: Vec<String>,
+        /// Add a tag, or several separated by commas. Can be repeated
+        #[arg(long = "tag", value_name = "TAG", value_delimiter = ',')]
+        tags: Vec<String>,
+        /// Remove a tag, or several separated by commas. Can be repeated
+        #[arg(long = "remove-tag", value_name = "TAG", value_delimiter = ',')]
+        remove_tags: Vec<String>,
+        /// Move the account to this folder, or out of its folder with ""
+        #[arg(long)]
+        folder: Option<String>,
+    },
+    Rm {
+        collection: String,
+        /// Account name or id
+        account: String,
+    },
+}
+
+#[derive(Args)]
+pub struct TwoFactorArgs {
+    /// Two-factor (TOTP) secret, in base32 or as an otpauth://totp/ URI
+    #[arg(long, conflicts_with = "hotp")]
+    totp: Option<String>,
+    /// Counter-based two-factor (HOTP) secret, in base32 or as an otpauth://hotp/ URI
+    #[arg(long)]
+    hotp: Option<String>,
+    /// The counter of the next HOTP code, if it isn't in the URI
+    #[arg(long, requires = "hotp")]
+    hotp_counter: Option<u64>,
+}
+
+impl TwoFactorArgs {
+    /// None if no secret was given
+    fn parse(&self) -> Result<Option<TwoFactor>, OtpError> {
+        if let Some(totp) = &self.totp {
+            return Ok(Some(TwoFactor::Totp(Totp::parse(totp)?)));
+        }
+        if let Some(hotp) = &self.hotp {
+            let mut hotp: Hotp = Hotp::parse(hotp)?;
+            if let Some(counter) = self.hotp_counter {
+                hotp.set_counter(counter);
+            }
+            return Ok(Some(TwoFactor::Hotp(hotp)));
+        }
+        Ok(None)
+    }
+}
+
+/// Which accounts a command works on. Without these, all of them
+#[derive(Args)]
+pub struct AccountFilter {
+    /// Only the accounts with this tag. Can be repeated, to require all of them
+    #[arg(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+    /// Only the accounts in this folder or its subfolders, or the ones in no folder with ""
+    #[arg(long)]
+    folder: Option<String>,
+}
+
+impl AccountFilter {
+    fn matches(&self, account: &Account) -> bool {
+        self.tags.iter().all(|tag| account.has_tag(tag)) &&
+            self.folder.as_ref().is_none_or(|folder| account.in_folder(folder))
+    }
+}
+
+#[derive(Subcommand)]
+pub enum BackupsCommand {
+    List {
+        collection: String,
+    },
+    /// Replace the accounts of a collection with the ones in one of its backups
+    Restore {
+        collection: String,
+        /// Backup number, as shown by "backups list"
+        number: usize,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Field {
+    Name,
+    Email,
+    Username,
+    Hints,
+    Password,
+    /// The current TOTP code
+    Totp,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum MatchArg {
+    Domain,
+    Host,
+    Prefix,
+}
+
+impl From<MatchArg> for UrlMatch {
+    fn from(match_by: MatchArg) -> Self {
+        match match_by {
+            MatchArg::Domain => UrlMatch::Domain,
+            MatchArg::Host => UrlMatch::Host,
+            MatchArg::Prefix => UrlMatch::Prefix,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CipherArg {
+    Xchacha20Poly1305,
+    Aes256Gcm,
+}
+
+impl From<CipherArg> for CipherSuite {
+    fn from(cipher: CipherArg) -> Self {
+        match cipher {
+            CipherArg::Xchacha20Poly1305 => CipherSuite::XChaCha20Poly1305,
+            CipherArg::Aes256Gcm => CipherSuite::Aes256Gcm,
+        }
+    }
+}
+
+impl PasswordSource {
+    fn read(&self) -> Result<String, Box<dyn Error>> {
+        if self.password_stdin {
+            return read_first_line(io::stdin().lock());
+        }
+        if let Some(var) = &self.password_env {
+            return match std::env::var(var) {
+                Ok(password) => Ok(password),
+                Err(_) => Err(format!("environment variable {} is not set", var).into()),
+            };
+        }
+        if let Some(fd) = self.password_fd {
+            return read_fd(fd);
+        }
+        Ok(
+            Password::new("Enter master password: ")
+                .without_confirmation()
+                .with_display_mode(inquire::PasswordDisplayMode::Masked)
+                .prompt()?
+        )
+    }
+}
+
+fn read_first_line<R: Read>(reader: R) -> Result<String, Box<dyn Error>> {
+    let mut line: String = String::new();
+    BufReader::new(reader).read_line(&mut line)?;
+    let len: usize = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+/// Opened through /dev/fd, so the descriptor is never taken over: it stays open (stdin included),
+/// and one that isn't open is just an error
+#[cfg(unix)]
+fn read_fd(fd: i32) -> Result<String, Box<dyn Error>> {
+    match fs::File::open(format!("/dev/fd/{}", fd)) {
+        Ok(file) => read_first_line(file),
+        Err(e) => Err(format!("can't read file descriptor {}: {}", fd, e).into()),
+    }
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: i32) -> Result<String, Box<dyn Error>> {
+    Err("--password-fd is only supported on Unix".into())
+}
+
+/// A collection whose accounts file was decrypted with the master password
+struct Unlocked {
+    collections: Vec<Collection>,
+    index: usize,
+    accounts: Vec<Account>,
+    session: Session,
+}
+
+impl Unlocked {
+    fn collection(&self) -> &Collection {
+        &self.collections[self.index]
+    }
+
+    fn account(&self, key: &str) -> Result<usize, Box<dyn Error>> {
+        match self.accounts.iter().position(|a| a.matches(key)) {
+            Some(i) => Ok(i),
+            None => Err(format!("account \"{}\" not found", key).into()),
+        }
+    }
+
+    fn save(&self, config: &Config) -> Result<(), Box<dyn Error>> {
+        save_accounts(
+            &self.accounts,
+            &self.collection().file_path,
+            &self.session,
+            config.backup_count
+        )?;
+        Ok(())
+    }
+}
+
+impl Drop for Unlocked {
+    fn drop(&mut self) {
+        self.accounts.zeroize();
+    }
+}
+
+fn find_collection(collections: &[Collection], name: &str) -> Result<usize, Box<dyn Error>> {
+    match collections.iter().position(|c| c.name == name) {
+        Some(i) => Ok(i),
+        None => Err(format!("collection \"{}\" not found", name).into()),
+    }
+}
+
+/// Reads the master password and decrypts the accounts of a collection. If the collection has
+/// no accounts file yet, it is only created when new_vault is given, with the master password
+/// strength rules of that config
+fn unlock(
+    name: &str,
+    source: &PasswordSource,
+    new_vault: Option<&Config>
+) -> Result<Unlocked, Box<dyn Error>> {
+    let collections: Vec<Collection> = get_collections(COLLECTIONS_FILE_PATH)?;
+    let index: usize = find_collection(&collections, name)?;
+    let collection: &Collection = &collections[index];
+
+    let mut password: String = source.read()?;
+    let unlocked: Result<(Vec<Account>, Session), Box<dyn Error>> = match
+        (fs::read(&collection.file_path), new_vault)
+    {
+        (Ok(data), _) => {
+            open_accounts(&data, password.as_bytes(), collection.salt.as_ref(), collection.cipher)
+                .map_err(Box::from)
+        }
+        (Err(e), Some(config)) if e.kind() == io::ErrorKind::NotFound => {
+            let strength: Strength = estimate(&password);
+            if strength.score >= config.min_master_password_score {
+                Ok(())
+            } else if config.refuse_weak_master_password {
+                Err(format!("the master password is {}", strength).into())
+            } else {
+                eprintln!("Warning: the master password is {}", strength);
+                Ok(())
+            }.and_then(|_| {
+                Session::new(password.as_bytes(), collection.cipher, &collection.kdf)
+                    .map(|s| (vec![], s))
+                    .map_err(Box::from)
+            })
+        }
+        (Err(e), _) => Err(e.into()),
+    };
+    password.zeroize();
+
+    let (accounts, session) = unlocked?;
+    Ok(Unlocked { collections, index, accounts, session })
+}
+
+pub fn run(command: Command, source: &PasswordSource) -> Result<(), Box<dyn Error>> {
+    let config: Config = get_config(CONFIG_FILE_PATH)?;
+
+    match command {
+        Command::Collections { action } => run_collections(action),
+        Command::Accounts { action } => run_accounts(*action, source, &config),
+        Command::Get { collection, account, field, custom } => {
+            let unlocked: Unlocked = unlock(&collection, source, None)?;
+            let account: &Account = &unlocked.accounts[unlocked.account(&account)?];
+            if let Some(name) = custom {
+                return match account.field(&name) {
+                    Some(field) => {
+                        println!("{}", field.value());
+                        Ok(())
+                    }
+                    None => Err(format!("{} has no field named {}", account.name, name).into()),
+                };
+            }
+            let value: String = match field {
+                Field::Name => account.name.clone(),
+                Field::Email => account.email().to_owned(),
+                Field::Username => account.username().to_owned(),
+                Field::Hints => account.hints().to_owned(),
+                Field::Password => account.password().to_owned(),
+                Field::Totp =>
+                    match account.totp() {
+                        Some(totp) => totp.now().0,
+                        None => {
+                            return Err(format!("{} has no TOTP secret", account.name).into());
+                        }
+                    }
+            };
+            println!("{}", value);
+            Ok(())
+        }
+        Command::Search { collection, query, verbose, filter } => {
+            let unlocked: Unlocked = unlock(&collection, source, None)?;
+            for hit in search(&unlocked.accounts, &query) {
+                let account: &Account = &unlocked.accounts[hit.index];
+                if !filter.matches(account) {
+                    continue;
+                }
+                if verbose {
+                    println!("{}\t{}\t{}", account.name, hit.field, hit.score);
+                } else {
+                    println!("{}", account.name);
+                }
+            }
+            Ok(())
+        }
+        Command::Find { collection, url, match_by, filter } => {
+            let url: Url = parse_url(&url)?;
+            let loosest: UrlMatch = match_by.into();
+            let unlocked: Unlocked = unlock(&collection, source, None)?;
+            let mut found: Vec<(UrlMatch, &Account)> = unlocked.accounts
+                .iter()
+                .filter(|account| filter.matches(account))
+                .filter_map(|account| {
+                    let best: UrlMatch = account
+                        .urls()
+                        .iter()
+                        .filter_map(|u| url_match(u, &url))
+                        .max()?;
+                    (best >= loosest).then_some((best, account))
+                })
+                .collect();
+            found.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.cmp(&b.1.name)));
+            for (_, account) in found {
+                println!("{}", account.name);
+            }
+            Ok(())
+        }
+        Command::Hotp { collection, account } => {
+            let mut unlocked: Unlocked = unlock(&collection, source, None)?;
+            let index: usize = unlocked.account(&account)?;
+            let file_path: &String = &unlocked.collections[unlocked.index].file_path;
+            match next_hotp_code(&mut unlocked.accounts, index, file_path, &unlocked.session)? {
+                Some(code) => println!("{}", code),
+                None => {
+                    return Err(format!("{} has no HOTP secret", account).into());
+                }
+            }
+            Ok(())
+        }
+        Command::Audit { collection } => {
+            let unlocked: Unlocked = unlock(&collection, source, None)?;
+            let report: AuditReport = audit(&unlocked.accounts, &config)?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            Ok(())
+        }
+        Command::BreachIndex { input, output } => {
+            let hashes: u64 = build_index(&input, &output)?;
+            println!("Indexed {} hashes into {}", hashes, output);
+            Ok(())
+        }
+        Command::Backups { action } => run_backups(action, source, &config),
+        Command::Generate {
+            length,
+            no_lowercase,
+            no_uppercase,
+            no_digits,
+            no_symbols,
+            exclude_ambiguous,
+        } => {
+            let classes: Vec<CharClass> = [
+                (CharClass::Lowercase, no_lowercase),
+                (CharClass::Uppercase, no_uppercase),
+                (CharClass::Digits, no_digits),
+                (CharClass::Symbols, no_symbols),
+            ]
+                .into_iter()
+                .filter(|(_, excluded)| !excluded)
+                .map(|(class, _)| class)
+                .collect();
+            println!("{}", generate(&PasswordOptions { length, classes, exclude_ambiguous })?);
+            Ok(())
+        }
+        Command::Passphrase { words, separator, capitalize, add_digit } => {
+            let options = PassphraseOptions { words, separator, capitalize, add_digit };
+            println!("{}", generate_passphrase(&options)?);
+            // on stderr, so it isn't captured along with the passphrase
+            eprintln!("{:.0} bits of entropy", options.entropy_bits());
+            Ok(())
+        }
+    }
+}
+
+/// Checks a URL given on the command line. It's saved as it was typed
+fn parse_url_arg(url: &str) -> Result<String, UrlError> {
+    parse_url(url).map(|_| url.to_owned())
+}
+
+fn run_collections(action: CollectionsCommand) -> Result<(), Box<dyn Error>> {
+    let mut collections: Vec<Collection> = get_collections(COLLECTIONS_FILE_PATH)?;
+
+    match action {
+        CollectionsCommand::List => {
+            for collection in &collections {
+                println!("{}\t{}", collection.name, collection.file_path);
+            }
+        }
+        CollectionsCommand::Add {
+            name,
+            file_path,
+            cipher,
+            calibrate,
+            memory,
+            iterations,
+            parallelism,
+        } => {
+            if collections.iter().any(|c| c.name == name) {
+                return Err(format!("collection \"{}\" already exists", name).into());
+            }
+            let kdf: KdfParams = if calibrate {
+                KdfParams::calibrate(Duration::from_secs(1))?
+            } else {
+                let default: KdfParams = KdfParams::default();
+                KdfParams {
+                    algorithm: KdfAlgorithm::Argon2id,
+                    m_cost: memory.map_or(default.m_cost, |m| m.saturating_mul(1024)),
+                    t_cost: iterations.unwrap_or(default.t_cost),
+                    p_cost: parallelism.unwrap_or(default.p_cost),
+                }
+            };
+            kdf.check()?;
+            collections.push(Collection::new(name, file_path, cipher.into(), kdf));
+            save_collections(&collections, COLLECTIONS_FILE_PATH)?;
+        }
+        CollectionsCommand::Rm { name, keep_file } => {
+            let index: usize = find_collection(&collections, &name)?;
+            let collection: Collection = collections.remove(index);
+            if !keep_file {
+                _ = fs::remove_file(&collection.file_path);
+                remove_backups(&collection.file_path);
+            }
+            save_collections(&collections, COLLECTIONS_FILE_PATH)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_accounts(
+    action: AccountsCommand,
+    source: &PasswordSource,
+    config: &Config
+) -> Result<(), Box<dyn Error>> {
+    match action {
+        AccountsCommand::List { collection, filter } => {
+            let unlocked: Unlocked = unlock(&collection, source, None)?;
+            for account in unlocked.accounts.iter().filter(|a| filter.matches(a)) {
+                println!("{}", account.name);
+            }
+        }
+        AccountsCommand::Show { collection, account, show_password } => {
+            let unlocked: Unlocked = unlock(&collection, source, None)?;
+            unlocked.accounts[unlocked.account(&account)?].print_info(show_password);
+        }
+        AccountsCommand::Add {
+            collection,
+            name,
+            email,
+            username,
+            hints,
+            password,
+            two_factor,
+            urls,
+            custom,
+            tags,
+            folder,
+        } => {
+            let two_factor: TwoFactor = two_factor.parse()?.unwrap_or(TwoFactor::None);
+            let mut unlocked: Unlocked = unlock(&collection, source, Some(config))?;
+            let password: String = match password {
+                Some(p) => p,
+                None => Password::new("Account password: ").prompt()?,
+            };
+            let mut account = Account::new(name, email, username, hints, password, two_factor);
+            for url in urls {
+                account.add_url(url);
+            }
+            for field in custom {
+                account.set_field(field);
+            }
+            for tag in tags {
+                account.add_tag(&tag);
+            }
+            if let Some(folder) = folder {
+                account.set_folder(&folder);
+            }
+            unlocked.accounts.push(account);
+            unlocked.save(config)?;
+        }
+        AccountsCommand::Edit {
+            collection,
+            account,
+            name,
+            email,
+            username,
+            hints,
+            password,
+            two_factor,
+            remove_two_factor,
+            urls,
+            remove_urls,
+            custom,
+            remove_custom,
+            tags,
+            remove_tags,
+            folder,
+        } => {
+            let two_factor: Option<TwoFactor> = match two_factor.parse()? {
+                None if remove_two_factor => Some(TwoFactor::None),
+                two_factor => two_factor,
+            };
+            let mut unlocked: Unlocked = unlock(&collection, source, None)?;
+            let index: usize = unlocked.account(&account)?;
+            let account: &mut Account = &mut unlocked.accounts[index];
+            for url in remove_urls {
+                if !account.remove_url(&url) {
+                    return Err(format!("{} has no URL {}", account.name, url).into());
+                }
+            }
+            for url in urls {
+                account.add_url(url);
+            }
+            for field_name in remove_custom {
+                if !account.remove_field(&field_name) {
+                    return Err(format!("{} has no field named {}", account.name, field_name).into());
+                }
+            }
+            for field in custom {
+                account.set_field(field);
+            }
+            for tag in remove_tags {
+                if !account.remove_tag(&tag) {
+                    return Err(format!("{} has no tag {}", account.name, tag).into());
+                }
+            }
+            for tag in tags {
+                account.add_tag(&tag);
+            }
+            if let Some(folder) = folder {
+                account.set_folder(&folder);
+            }
+            account.update(name, email, username, hints, password, two_factor);
+            unlocked.save(config)?;
+        }
+        AccountsCommand::Rm { collection, account } => {
+            let mut unlocked: Unlocked = unlock(&collection, source, None)?;
+            let index: usize = unlocked.account(&account)?;
+            unlocked.accounts.remove(index).zeroize();
+            unlocked.save(config)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_backups(
+    action: BackupsCommand,
+    source: &PasswordSource,
+    config: &Config
+) -> Result<(), Box<dyn Error>> {
+    match action {
+        BackupsCommand::List { collection } => {
+            let collections: Vec<Collection> = get_collections(COLLECTIONS_FILE_PATH)?;
+            let index: usize = find_collection(&collections, &collection)?;
+            for backup in list_backups(&collections[index].file_path) {
+                println!("{}\t{}", backup, backup.path);
+            }
+        }
+        BackupsCommand::Restore { collection, number } => {
+            let collections: Vec<Collection> = get_collections(COLLECTIONS_FILE_PATH)?;
+            let index: usize = find_collection(&collections, &collection)?;
+            let c: &Collection = &collections[index];
+            let data: Vec<u8> = match fs::read(backup_path(&c.file_path, number)) {
+                Ok(d) => d,
+                Err(_) => {
+                    return Err(format!("backup {} not found", number).into());
+                }
+            };
+
+            // the accounts file isn't needed, so a missing or corrupt one can still be restored
+            let mut password: String = source.read()?;
+            let restored = unlock_backup(&data, &c.file_path, password.as_bytes(), c.salt.as_ref(), c.cipher);
+            password.zeroize();
+
+            let (accounts, session) = match restored {
+                Ok(r) => r,
+                Err(VaultError::WrongPassword) => {
+                    return Err(
+                        "incorrect password, or the accounts file doesn't take the same one as the backup (if it's damaged, move it away first)".into()
+                    );
+                }
+                Err(e) => {
+                    return Err(e.into());
+                }
+            };
+            let unlocked = Unlocked { collections, index, accounts, session };
+            unlocked.save(config)?;
+            println!("Restored {} accounts from backup {}", unlocked.accounts.len(), number);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use cli_clipboard::{ ClipboardContext, ClipboardProvider };
+use crossterm::{ cursor, event::{ self, Event, KeyCode, KeyEventKind }, queue, style::Print, terminal::{ self, ClearType } };
+use std::io::{ self, Write };
+use std::time::{ Duration, Instant };
+use zeroize::Zeroize;
+
+use crate::error::ClipboardError;
+use crate::utils::RawMode;
+
+/// Somewhere values can be copied to and read back from
+pub trait Clipboard {
+    fn get(&mut self) -> Result<String, ClipboardError>;
+    fn set(&mut self, contents: &str) -> Result<(), ClipboardError>;
+}
+
+/// The desktop's clipboard. On Wayland and X11, what is copied is only available while this
+/// exists, so it's kept until the program exits
+pub struct SystemClipboard {
+    context: ClipboardContext,
+}
+
+impl SystemClipboard {
+    /// Fails if there is no clipboard, like over SSH or without a display server
+    pub fn new() -> Result<SystemClipboard, ClipboardError> {
+        match ClipboardContext::new() {
+            Ok(context) => Ok(SystemClipboard { context }),
+            Err(e) => Err(ClipboardError::Unavailable(e.to_string())),
+        }
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn get(&mut self) -> Result<String, ClipboardError> {
+        self.context.get_contents().map_err(|e| ClipboardError::Read(e.to_string()))
+    }
+
+    fn set(&mut self, contents: &str) -> Result<(), ClipboardError> {
+        self.context.set_contents(contents.to_owned()).map_err(|e| ClipboardError::Write(e.to_string()))
+    }
+}
+
+/// A clipboard that only exists in memory, so copying can be tested without touching the
+/// desktop's one
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryClipboard {
+    contents: String,
+}
+
+#[cfg(test)]
+impl Clipboard for MemoryClipboard {
+    fn get(&mut self) -> Result<String, ClipboardError> {
+        Ok(self.contents.clone())
+    }
+
+    fn set(&mut self, contents: &str) -> Result<(), ClipboardError> {
+        self.contents.zeroize();
+        self.contents = contents.to_owned();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Drop for MemoryClipboard {
+    fn drop(&mut self) {
+        self.contents.zeroize();
+    }
+}
+
+/// Empties the clipboard, but only if it still holds copied: if the user copied something else
+/// since, it's left alone. Returns true if it was emptied
+pub fn clear_if_unchanged(clipboard: &mut dyn Clipboard, copied: &str) -> Result<bool, ClipboardError> {
+    // some platforms fail to read an empty clipboard, which isn't holding our value either
+    let mut contents: String = match clipboard.get() {
+        Ok(contents) => contents,
+        Err(_) => {
+            return Ok(false);
+        }
+    };
+    let unchanged: bool = contents == copied;
+    contents.zeroize();
+    if unchanged {
+        clipboard.set("")?;
+    }
+    Ok(unchanged)
+}
+
+/// Copies value, and if seconds isn't 0, counts down until the clipboard is cleared with
+/// clear_if_unchanged. Pressing any key clears it right away. what is shown to the user, like
+/// "password"
+pub fn copy_with_countdown(
+    clipboard: &mut dyn Clipboard,
+    value: &str,
+    what: &str,
+    seconds: u64
+) -> Result<(), ClipboardError> {
+    copy_sequence(clipboard, &[(what, value)], seconds)
+}
+
+/// Copies several values one after the other, like the username and then the password, so they
+/// can be pasted into a login form without going back to the menu. Each key press copies the next
+/// one, and ESC stops. The countdown of copy_with_countdown restarts with every value, and when
+/// it ends the clipboard is cleared and the rest aren't copied
+pub fn copy_sequence(
+    clipboard: &mut dyn Clipboard,
+    values: &[(&str, &str)],
+    seconds: u64
+) -> Result<(), ClipboardError> {
+    run_sequence(clipboard, values, seconds, wait_for_key)
+}
+
+/// Builds the line shown while waiting for a key, from the seconds left (None without a deadline)
+type Message<'a> = &'a dyn Fn(Option<u64>) -> String;
+
+/// The steps of copy_sequence. wait is called after each value is copied, and returns the key
+/// that was pressed, or None if the deadline passed, like wait_for_key does in the terminal
+fn run_sequence(
+    clipboard: &mut dyn Clipboard,
+    values: &[(&str, &str)],
+    seconds: u64,
+    mut wait: impl FnMut(Option<Instant>, Message<'_>) -> Result<Option<KeyCode>, ClipboardError>
+) -> Result<(), ClipboardError> {
+    for (index, (what, value)) in values.iter().enumerate() {
+        clipboard.set(value)?;
+        let next: Option<&str> = values.get(index + 1).map(|(next, _)| *next);
+        if seconds == 0 && next.is_none() {
+            println!("Copied the {} to the clipboard", what);
+            return Ok(());
+        }
+
+        let deadline: Option<Instant> = (seconds > 0).then(|| Instant::now() + Duration::from_secs(seconds));
+        let key: Option<KeyCode> = wait(deadline, &|remaining| {
+            let mut message: String = format!("Copied the {} to the clipboard, ", what);
+            match (next, remaining) {
+                (Some(next), Some(remaining)) => {
+                    message += &format!(
+                        "press any key to copy the {} (ESC to stop, cleared in {}s)",
+                        next,
+                        remaining
+                    );
+                }
+                (Some(next), None) => {
+                    message += &format!("press any key to copy the {} (ESC to stop)", next);
+                }
+                (None, _) => {
+                    message += &format!(
+                        "it will be cleared in {}s (press any key to clear it now)",
+                        remaining.unwrap_or_default()
+                    );
+                }
+            }
+            message
+        })?;
+
+        if next.is_some() && key.is_some_and(|key| key != KeyCode::Esc) {
+            continue;
+        }
+        if seconds > 0 {
+            if clear_if_unchanged(clipboard, value)? {
+                println!("The clipboard was cleared");
+            } else {
+                println!("The clipboard changed since the {} was copied, so it was left as it is", what);
+            }
+        }
+        return Ok(());
+    }
+    Ok(())
+}
+
+/// Shows message, with the seconds left until deadline, until a key is pressed or the deadline
+/// passes. Without a deadline, it waits for a key. Returns the key, or None if the time ran out
+fn wait_for_key(
+    deadline: Option<Instant>,
+    message: Message<'_>
+) -> Result<Option<KeyCode>, ClipboardError> {
+    let _raw: RawMode = RawMode::enable()?;
+    let mut stdout = io::stdout();
+    let key: Option<KeyCode> = loop {
+        let remaining: Option<Duration> = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        if remaining.is_some_and(|r| r.is_zero()) {
+            break None;
+        }
+        queue!(
+            stdout,
+            cursor::MoveToColumn(0),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(message(remaining.map(|r| r.as_secs() + 1)))
+        )?;
+        stdout.flush()?;
+        // redrawn often enough that the seconds don't skip
+        let wait: Duration = remaining.map_or(Duration::from_millis(200), |r| r.min(Duration::from_millis(200)));
+        if event::poll(wait)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    break Some(key.code);
+                }
+            }
+        }
+    };
+    queue!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
+    stdout.flush()?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for wait_for_key: returns the given keys in order, and records the messages
+    fn scripted(
+        keys: Vec<Option<KeyCode>>,
+        messages: &mut Vec<String>
+    ) -> impl FnMut(Option<Instant>, Message<'_>) -> Result<Option<KeyCode>, ClipboardError> + '_ {
+        let mut keys = keys.into_iter();
+        move |_, message| {
+            messages.push(message(Some(30)));
+            Ok(keys.next().expect("waited more times than expected"))
+        }
+    }
+
+    #[test]
+    fn clears_the_clipboard_if_it_still_holds_the_value() {
+        let mut clipboard: MemoryClipboard = MemoryClipboard::default();
+        clipboard.set("secret").unwrap();
+        assert!(clear_if_unchanged(&mut clipboard, "secret").unwrap());
+        assert_eq!(clipboard.get().unwrap(), "");
+    }
+
+    #[test]
+    fn leaves_the_clipboard_alone_if_it_changed() {
+        let mut clipboard: MemoryClipboard = MemoryClipboard::default();
+        clipboard.set("secret").unwrap();
+        clipboard.set("something else").unwrap();
+        assert!(!clear_if_unchanged(&mut clipboard, "secret").unwrap());
+        assert_eq!(clipboard.get().unwrap(), "something else");
+    }
+
+    #[test]
+    fn a_single_value_is_cleared_when_the_time_runs_out_or_a_key_is_pressed() {
+        for key in [None, Some(KeyCode::Char('x'))] {
+            let mut clipboard: MemoryClipboard = MemoryClipboard::default();
+            let mut messages: Vec<String> = vec![];
+            run_sequence(&mut clipboard, &[("password", "secret")], 30, scripted(vec![key], &mut messages)).unwrap();
+            assert_eq!(clipboard.get().unwrap(), "");
+            assert_eq!(messages.len(), 1);
+            assert!(messages[0].contains("cleared in 30s"));
+        }
+    }
+
+    #[test]
+    fn nothing_is_cleared_or_waited_for_with_0_seconds() {
+        let mut clipboard: MemoryClipboard = MemoryClipboard::default();
+        let mut messages: Vec<String> = vec![];
+        run_sequence(&mut clipboard, &[("password", "secret")], 0, scripted(vec![], &mut messages)).unwrap();
+        assert_eq!(clipboard.get().unwrap(), "secret");
+        assert!(messages.is_empty());
+    }
+
+    #[test]
+    fn a_key_press_copies_the_next_value() {
+        let mut clipboard: MemoryClipboard = MemoryClipboard::default();
+        let mut messages: Vec<String> = vec![];
+        let values: [(&str, &str); 2] = [("username", "alice"), ("password", "secret")];
+        let keys: Vec<Option<KeyCode>> = vec![Some(KeyCode::Char('x')), None];
+        run_sequence(&mut clipboard, &values, 30, scripted(keys, &mut messages)).unwrap();
+        assert_eq!(clipboard.get().unwrap(), "");
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("Copied the username") && messages[0].contains("copy the password"));
+        assert!(messages[1].starts_with("Copied the password"));
+    }
+
+    #[test]
+    fn escape_or_the_time_running_out_stops_the_sequence() {
+        for key in [Some(KeyCode::Esc), None] {
+            let mut clipboard: MemoryClipboard = MemoryClipboard::default();
+            let mut messages: Vec<String> = vec![];
+            let values: [(&str, &str); 2] = [("username", "alice"), ("password", "secret")];
+            run_sequence(&mut clipboard, &values, 30, scripted(vec![key], &mut messages)).unwrap();
+            // the password was never copied, and the username was cleared
+            assert_eq!(clipboard.get().unwrap(), "");
+            assert_eq!(messages.len(), 1);
+        }
+    }
+
+    #[test]
+    fn with_0_seconds_the_sequence_waits_for_keys_and_keeps_the_last_value() {
+        let mut clipboard: MemoryClipboard = MemoryClipboard::default();
+        let mut messages: Vec<String> = vec![];
+        let values: [(&str, &str); 2] = [("username", "alice"), ("password", "secret")];
+        run_sequence(&mut clipboard, &values, 0, scripted(vec![Some(KeyCode::Enter)], &mut messages)).unwrap();
+        assert_eq!(clipboard.get().unwrap(), "secret");
+        assert_eq!(messages.len(), 1);
+    }
+}
@@ -1,29 +1,61 @@
-use std::error::Error;
 use std::fs;
-use inquire::{ InquireError, Text };
-use rand::{ rngs::OsRng, RngCore };
+use inquire::{ CustomType, InquireError, Select, Text };
 use serde::{ Deserialize, Serialize };
 use uuid::Uuid;
 use zeroize::Zeroize;
 
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::cryptography::{ CipherSuite, KdfAlgorithm, KdfParams };
+use crate::error::VaultError;
+use crate::utils::write_atomic;
+
 #[derive(Serialize, Deserialize, Debug, Zeroize)]
 pub struct Collection {
     id: String,
     pub name: String,
     pub file_path: String,
-    pub salt: [u8; 32],
+    /// Salt of legacy (v0) accounts files. Newer files keep their salt in their own header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<[u8; 32]>,
+    /// Cipher used to create the accounts file
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub cipher: CipherSuite,
+    /// Argon2 parameters used to create (or re-key) the accounts file. Once it's created,
+    /// the ones saved in its header are the ones used to decrypt it
+    #[serde(default)]
+    #[zeroize(skip)]
+    pub kdf: KdfParams,
 }
 
-impl Collection {
-    fn new(name: String, file_path: String) -> Collection {
-        let mut salt: [u8; 32] = [0u8; 32];
-        OsRng.fill_bytes(&mut salt);
+enum KdfStrength {
+    Default,
+    Calibrate,
+    Custom,
+}
+
+impl fmt::Display for KdfStrength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KdfStrength::Default => write!(f, "Default ({})", KdfParams::default()),
+            KdfStrength::Calibrate => write!(f, "Calibrate to ~1 second on this machine"),
+            KdfStrength::Custom => write!(f, "Custom"),
+        }
+    }
+}
 
+impl Collection {
+    pub fn new(name: String, file_path: String, cipher: CipherSuite, kdf: KdfParams) -> Collection {
         Collection {
             id: Uuid::new_v4().to_string(),
             name,
             file_path,
-            salt,
+            salt: None,
+            cipher,
+            kdf,
         }
     }
 
@@ -34,7 +66,18 @@ impl Collection {
         let file_path: String = Text::new("Collection file file_path: ")
             .with_help_message("This is where your passwords will be saved")
             .prompt()?;
-        Ok(Collection::new(name, file_path))
+        let cipher: CipherSuite = Select::new(
+            "Encryption algorithm: ",
+            vec![CipherSuite::XChaCha20Poly1305, CipherSuite::Aes256Gcm]
+        ).prompt()?;
+        let kdf: KdfParams = prompt_kdf_params()?;
+        Ok(Collection::new(name, file_path, cipher, kdf))
+    }
+
+    /// Changes the key derivation parameters. The accounts file must be saved again with
+    /// a new session derived with them right after this
+    pub fn rekey(&mut self, kdf: KdfParams) {
+        self.kdf = kdf;
     }
 
     pub fn edit(&mut self) -> Result<(), InquireError> {
@@ -58,27 +101,76 @@ impl Collection {
     }
 }
 
-pub fn get_collections(path: &str) -> Result<Vec<Collection>, Box<dyn Error>> {
+/// Asks for the Argon2 parameters used to derive the key of a collection's accounts file.
+/// Stronger parameters make the master password harder to brute force, but unlocking slower
+pub fn prompt_kdf_params() -> Result<KdfParams, InquireError> {
+    let strength: KdfStrength = Select::new(
+        "Key derivation strength: ",
+        vec![KdfStrength::Default, KdfStrength::Calibrate, KdfStrength::Custom]
+    ).prompt()?;
+
+    match strength {
+        KdfStrength::Default => Ok(KdfParams::default()),
+        KdfStrength::Calibrate => {
+            println!("Calibrating...");
+            match KdfParams::calibrate(Duration::from_secs(1)) {
+                Ok(kdf) => {
+                    println!("Using {}", kdf);
+                    Ok(kdf)
+                }
+                Err(e) => Err(InquireError::Custom(Box::new(e))),
+            }
+        }
+        KdfStrength::Custom => {
+            loop {
+                let memory: u32 = CustomType::new("Memory (MiB): ")
+                    .with_default(KdfParams::default().m_cost / 1024)
+                    .prompt()?;
+                let iterations: u32 = CustomType::new("Iterations: ")
+                    .with_default(KdfParams::default().t_cost)
+                    .prompt()?;
+                let parallelism: u32 = CustomType::new("Parallelism: ")
+                    .with_default(KdfParams::default().p_cost)
+                    .prompt()?;
+                let kdf = KdfParams {
+                    algorithm: KdfAlgorithm::Argon2id,
+                    m_cost: memory.saturating_mul(1024),
+                    t_cost: iterations,
+                    p_cost: parallelism,
+                };
+                match kdf.check() {
+                    Ok(_) => {
+                        return Ok(kdf);
+                    }
+                    Err(e) => println!("Invalid parameters: {}. Try again", e),
+                }
+            }
+        }
+    }
+}
+
+pub fn get_collections(path: &str) -> Result<Vec<Collection>, VaultError> {
     let file_stream: Vec<u8> = match fs::read(path) {
         Ok(stream) => stream,
-        Err(_) => {
-            // file_path does not exist
-            _ = fs::write(path, "[]")?;
-            fs::read(path).unwrap()
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            write_atomic(path, b"[]")?;
+            fs::read(path)?
+        }
+        // any other error (like missing permissions) must not replace the collections, or the
+        // salts of legacy accounts files would be lost with them
+        Err(e) => {
+            return Err(e.into());
         }
     };
 
     // println!("{}", String::from_utf8(file_stream.clone()).unwrap());
 
-    match serde_json::from_slice(&file_stream) {
-        Ok(v) => Ok(v),
-        Err(e) => Err(Box::new(e)),
-    }
+    Ok(serde_json::from_slice(&file_stream)?)
 }
 
-pub fn save_collections(collections: &Vec<Collection>, path: &str) -> Result<(), Box<dyn Error>> {
-    let serialized: String = serde_json::to_string(&collections).unwrap();
+pub fn save_collections(collections: &Vec<Collection>, path: &str) -> Result<(), VaultError> {
+    let serialized: String = serde_json::to_string(&collections)?;
 
-    fs::write(path, serialized)?;
+    write_atomic(path, serialized.as_bytes())?;
     Ok(())
 }
@@ -0,0 +1,54 @@
+use serde::{ Deserialize, Serialize };
+use std::fs;
+use std::io;
+
+use crate::error::VaultError;
+use crate::utils::write_atomic;
+
+/// User settings. Missing values take their default, so older config files keep working
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// How many encrypted backups are kept next to each accounts file (0 disables them)
+    pub backup_count: usize,
+    /// New master passwords scoring below this (0 very weak to 4 very strong) need confirmation
+    pub min_master_password_score: u8,
+    /// Refuse weak master passwords instead of asking for confirmation
+    pub refuse_weak_master_password: bool,
+    /// The audit reports passwords that haven't changed in more than this many days (0 disables it)
+    pub max_password_age_days: u64,
+    /// A local copy of the Have I Been Pwned SHA-1 password list (the text file ordered by hash, or
+    /// an index built from it). When set, the audit reports passwords that appear in it
+    pub breached_passwords_file: Option<String>,
+    /// Copied passwords and fields are cleared from the clipboard after this many seconds, if it
+    /// still holds them (0 never clears them)
+    pub clipboard_clear_seconds: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            backup_count: 5,
+            min_master_password_score: 3,
+            refuse_weak_master_password: false,
+            max_password_age_days: 365,
+            breached_passwords_file: None,
+            clipboard_clear_seconds: 30,
+        }
+    }
+}
+
+/// Reads the config file. If it doesn't exist, it is created with the default settings. Any other
+/// error reading it is returned, so it's never overwritten
+pub fn get_config(path: &str) -> Result<Config, VaultError> {
+    match fs::read(path) {
+        Ok(stream) => Ok(serde_json::from_slice(&stream)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let config: Config = Config::default();
+            write_atomic(path, serde_json::to_string_pretty(&config)?.as_bytes())?;
+            Ok(config)
+        }
+        // an unreadable config is reported rather than replaced with the defaults
+        Err(e) => Err(e.into()),
+    }
+}
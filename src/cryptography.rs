@@ -1,123 +1,663 @@
-use argon2::Argon2;
-use crypto::aead::{ AeadDecryptor, AeadEncryptor };
-use crypto::aes_gcm::AesGcm;
-use std::error::Error;
-use std::io::ErrorKind;
+use aes_gcm::aead::generic_array::typenum::Unsigned;
+use aes_gcm::aead::{ Aead, KeyInit, Nonce, Payload };
+use aes_gcm::{ Aes128Gcm, Aes256Gcm };
+use argon2::{ Algorithm, Argon2, Params, Version };
+use chacha20poly1305::XChaCha20Poly1305;
+use serde::{ Deserialize, Serialize };
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::{ Duration, Instant };
+use zeroize::Zeroize;
 
-fn key_derivation(password: &[u8], salt: &[u8; 32]) -> Result<[u8; 32], argon2::Error> {
-    let mut output_key: [u8; 32] = [0u8; 32];
+use crate::error::VaultError;
+use crate::random;
 
-    Argon2::default().hash_password_into(&password, salt, &mut output_key)?;
+/// First bytes of every vault file written with a header. Files that don't start with
+/// these bytes are treated as legacy ("v0") [hexNonce]/[hexCipher]/[hexMac] strings
+pub const MAGIC: &[u8; 4] = b"RPMV";
 
-    Ok(output_key)
+/// Version of the vault file layout written by encrypt()
+pub const FORMAT_VERSION: u8 = 1;
+
+const SALT_LEN: usize = 32;
+const MAC_LEN: usize = 16;
+
+/// Cipher used to encrypt the body of a vault file. The discriminant is the id saved in the header
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CipherSuite {
+    /// AES-GCM with a 128 bit key (the first half of the derived key). This is what v0 files use,
+    /// it's only kept so they can still be decrypted
+    Aes128Gcm = 1,
+    Aes256Gcm = 2,
+    /// Default for new vaults: its 192 bit random nonces can't realistically collide, no matter
+    /// how many times a vault is saved with the same key
+    #[default]
+    XChaCha20Poly1305 = 3,
+}
+
+impl CipherSuite {
+    fn from_id(id: u8) -> Option<CipherSuite> {
+        match id {
+            1 => Some(CipherSuite::Aes128Gcm),
+            2 => Some(CipherSuite::Aes256Gcm),
+            3 => Some(CipherSuite::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    fn cipher(&self) -> &'static dyn Cipher {
+        match self {
+            CipherSuite::Aes128Gcm => &AES_128_GCM,
+            CipherSuite::Aes256Gcm => &AES_256_GCM,
+            CipherSuite::XChaCha20Poly1305 => &XCHACHA20_POLY1305,
+        }
+    }
+}
+
+impl fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CipherSuite::Aes128Gcm => write!(f, "AES-128-GCM (legacy)"),
+            CipherSuite::Aes256Gcm => write!(f, "AES-256-GCM"),
+            CipherSuite::XChaCha20Poly1305 => write!(f, "XChaCha20-Poly1305"),
+        }
+    }
+}
+
+/// An AEAD cipher. It encrypts the body of a vault file with the key derived from the password,
+/// and authenticates it together with the aad (the vault header)
+pub trait Cipher {
+    fn nonce_len(&self) -> usize;
+
+    /// Returns the cipher text followed by the mac
+    fn encrypt(
+        &self,
+        key: &[u8; 32],
+        nonce: &[u8],
+        aad: &[u8],
+        data: &[u8]
+    ) -> Result<Vec<u8>, VaultError>;
+
+    /// data is the cipher text followed by the mac. Fails with WrongPassword if the mac
+    /// doesn't match, which means the password is wrong or the file was modified
+    fn decrypt(
+        &self,
+        key: &[u8; 32],
+        nonce: &[u8],
+        aad: &[u8],
+        data: &[u8]
+    ) -> Result<Vec<u8>, VaultError>;
+}
+
+/// Cipher implementation for any of the RustCrypto AEADs. Only the first key_len bytes of the
+/// derived key are used
+struct AeadCipher<C> {
+    key_len: usize,
+    algorithm: PhantomData<C>,
+}
+
+static AES_128_GCM: AeadCipher<Aes128Gcm> = AeadCipher { key_len: 16, algorithm: PhantomData };
+static AES_256_GCM: AeadCipher<Aes256Gcm> = AeadCipher { key_len: 32, algorithm: PhantomData };
+static XCHACHA20_POLY1305: AeadCipher<XChaCha20Poly1305> = AeadCipher {
+    key_len: 32,
+    algorithm: PhantomData,
+};
+
+impl<C: Aead + KeyInit> Cipher for AeadCipher<C> {
+    fn nonce_len(&self) -> usize {
+        C::NonceSize::USIZE
+    }
+
+    fn encrypt(
+        &self,
+        key: &[u8; 32],
+        nonce: &[u8],
+        aad: &[u8],
+        data: &[u8]
+    ) -> Result<Vec<u8>, VaultError> {
+        let cipher: C = match C::new_from_slice(&key[..self.key_len]) {
+            Ok(c) => c,
+            Err(_) => {
+                return Err(VaultError::Encryption);
+            }
+        };
+        match cipher.encrypt(Nonce::<C>::from_slice(nonce), Payload { msg: data, aad }) {
+            Ok(encrypted) => Ok(encrypted),
+            Err(_) => Err(VaultError::Encryption),
+        }
+    }
+
+    fn decrypt(
+        &self,
+        key: &[u8; 32],
+        nonce: &[u8],
+        aad: &[u8],
+        data: &[u8]
+    ) -> Result<Vec<u8>, VaultError> {
+        let cipher: C = match C::new_from_slice(&key[..self.key_len]) {
+            Ok(c) => c,
+            Err(_) => {
+                return Err(VaultError::Encryption);
+            }
+        };
+        match cipher.decrypt(Nonce::<C>::from_slice(nonce), Payload { msg: data, aad }) {
+            Ok(decrypted) => Ok(decrypted),
+            Err(_) => Err(VaultError::WrongPassword),
+        }
+    }
+}
+
+/// Argon2 variant used to derive the key. The discriminant is the id saved in the header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KdfAlgorithm {
+    Argon2d = 0,
+    Argon2i = 1,
+    Argon2id = 2,
+}
+
+impl KdfAlgorithm {
+    fn from_id(id: u8) -> Option<KdfAlgorithm> {
+        match id {
+            0 => Some(KdfAlgorithm::Argon2d),
+            1 => Some(KdfAlgorithm::Argon2i),
+            2 => Some(KdfAlgorithm::Argon2id),
+            _ => None,
+        }
+    }
+
+    fn argon2_algorithm(&self) -> Algorithm {
+        match self {
+            KdfAlgorithm::Argon2d => Algorithm::Argon2d,
+            KdfAlgorithm::Argon2i => Algorithm::Argon2i,
+            KdfAlgorithm::Argon2id => Algorithm::Argon2id,
+        }
+    }
+}
+
+/// The most memory (in KiB), iterations and parallelism a vault file can use. The header is only
+/// authenticated once the key is derived, so without them a modified file could make the key
+/// derivation use all the memory of the machine, or never end
+const MAX_M_COST: u32 = 4 * 1024 * 1024;
+const MAX_T_COST: u32 = 1000;
+const MAX_P_COST: u32 = 64;
+
+/// Argon2 parameters: memory in KiB (m), iterations (t) and parallelism (p)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub algorithm: KdfAlgorithm,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    /// The parameters used by Argon2::default(), which is what v0 files were encrypted with
+    fn default() -> Self {
+        KdfParams {
+            algorithm: KdfAlgorithm::Argon2id,
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Checks that argon2 accepts these parameters, and that they are within the limits
+    pub fn check(&self) -> Result<(), VaultError> {
+        if let Some(limit) = self.over_limit() {
+            return Err(VaultError::KdfOverLimit(limit));
+        }
+        Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))?;
+        Ok(())
+    }
+
+    /// Describes the limit these parameters go over, if any
+    fn over_limit(&self) -> Option<&'static str> {
+        if self.m_cost > MAX_M_COST {
+            Some("more than 4 GiB of memory")
+        } else if self.t_cost > MAX_T_COST {
+            Some("more than 1000 iterations")
+        } else if self.p_cost > MAX_P_COST {
+            Some("a parallelism above 64")
+        } else {
+            None
+        }
+    }
+
+    /// Finds Argon2id parameters that take around "target" to derive a key on this machine.
+    /// It starts with 64 MiB of memory and a single iteration, and then scales the number of
+    /// iterations (or the memory, if a single iteration is already too slow)
+    pub fn calibrate(target: Duration) -> Result<KdfParams, VaultError> {
+        let mut params = KdfParams {
+            algorithm: KdfAlgorithm::Argon2id,
+            m_cost: 64 * 1024,
+            t_cost: 1,
+            p_cost: 1,
+        };
+
+        loop {
+            let start: Instant = Instant::now();
+            derive_key(b"calibration", &[0u8; SALT_LEN], &params)?;
+            let elapsed: Duration = start.elapsed();
+
+            if elapsed > target && params.m_cost / 2 >= Params::DEFAULT_M_COST {
+                params.m_cost /= 2;
+                continue;
+            }
+
+            let scale: f64 = target.as_secs_f64() / elapsed.as_secs_f64().max(0.001);
+            params.t_cost = (scale.round() as u32).clamp(1, MAX_T_COST);
+            return Ok(params);
+        }
+    }
+}
+
+impl fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?}, {} MiB of memory, {} iterations, parallelism {}",
+            self.algorithm,
+            self.m_cost / 1024,
+            self.t_cost,
+            self.p_cost
+        )
+    }
+}
+
+/// Everything needed to decrypt a vault file, except for the password.
+/// Binary layout (integers are little endian):
+/// [magic: 4][version: 1][cipher: 1][kdf algorithm: 1][m: 4][t: 4][p: 4][salt len: 1][salt][nonce len: 1][nonce]
+/// The header is followed by the cipher text and the mac, and it is authenticated as the aad
+#[derive(Debug)]
+pub struct VaultHeader {
+    pub version: u8,
+    pub cipher: CipherSuite,
+    pub kdf: KdfParams,
+    pub salt: [u8; SALT_LEN],
+    pub nonce: Vec<u8>,
+}
+
+impl VaultHeader {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::from(&MAGIC[..]);
+        bytes.push(self.version);
+        bytes.push(self.cipher as u8);
+        bytes.push(self.kdf.algorithm as u8);
+        bytes.extend_from_slice(&self.kdf.m_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.t_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.kdf.p_cost.to_le_bytes());
+        bytes.push(self.salt.len() as u8);
+        bytes.extend_from_slice(&self.salt);
+        bytes.push(self.nonce.len() as u8);
+        bytes.extend_from_slice(&self.nonce);
+        bytes
+    }
+
+    /// Parses the header at the start of data. Returns the header and its length in bytes
+    fn parse(data: &[u8]) -> Result<(VaultHeader, usize), VaultError> {
+        let mut reader = ByteReader { data, pos: MAGIC.len() };
+
+        let version: u8 = reader.u8()?;
+        if version != FORMAT_VERSION {
+            return Err(VaultError::UnsupportedVersion(version));
+        }
+        let cipher: CipherSuite = match CipherSuite::from_id(reader.u8()?) {
+            Some(c) => c,
+            None => {
+                return Err(VaultError::CorruptFile("unknown cipher suite"));
+            }
+        };
+        let algorithm: KdfAlgorithm = match KdfAlgorithm::from_id(reader.u8()?) {
+            Some(a) => a,
+            None => {
+                return Err(VaultError::CorruptFile("unknown key derivation algorithm"));
+            }
+        };
+        let kdf = KdfParams {
+            algorithm,
+            m_cost: reader.u32()?,
+            t_cost: reader.u32()?,
+            p_cost: reader.u32()?,
+        };
+        if kdf.over_limit().is_some() {
+            return Err(VaultError::CorruptFile("the key derivation parameters are above the limits"));
+        }
+
+        let salt_len: usize = reader.u8()? as usize;
+        let salt: [u8; SALT_LEN] = match reader.bytes(salt_len)?.try_into() {
+            Ok(s) => s,
+            Err(_) => {
+                return Err(VaultError::CorruptFile("invalid salt length"));
+            }
+        };
+
+        let nonce_len: usize = reader.u8()? as usize;
+        if nonce_len != cipher.cipher().nonce_len() {
+            return Err(VaultError::CorruptFile("invalid nonce length"));
+        }
+        let nonce: Vec<u8> = reader.bytes(nonce_len)?.to_vec();
+
+        Ok((VaultHeader { version, cipher, kdf, salt, nonce }, reader.pos))
+    }
+}
+
+/// Small cursor over the header bytes. Running out of data means the file is corrupt
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
 }
 
-// https://stackoverflow.com/questions/43439771/how-do-i-create-an-empty-byte-array
+impl<'a> ByteReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], VaultError> {
+        match self.data.get(self.pos..self.pos + len) {
+            Some(b) => {
+                self.pos += len;
+                Ok(b)
+            }
+            None => Err(VaultError::CorruptFile("the header is incomplete")),
+        }
+    }
 
-/// Creates an initial vector (iv). This is also called a nonce
-fn get_iv(size: usize) -> Vec<u8> {
-    let mut iv: Vec<u8> = vec![];
-    for _ in 0..size {
-        let r: u8 = rand::random();
-        iv.push(r);
+    fn u8(&mut self) -> Result<u8, VaultError> {
+        Ok(self.bytes(1)?[0])
     }
 
-    iv
+    fn u32(&mut self) -> Result<u32, VaultError> {
+        let mut buf: [u8; 4] = [0u8; 4];
+        buf.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
 }
 
-///encrypt "data" using "password" as the password
-/// Output is [hexNonce]/[hexCipher]/[hexMac] (nonce and iv are the same thing)
-pub fn encrypt(data: &[u8], password: &[u8], salt: &[u8; 32]) -> Result<String, argon2::Error> {
-    let key_size = crypto::aes::KeySize::KeySize128;
+/// A key derived from a master password. It lives in its own heap allocation, which is locked
+/// in memory (so it is never written to swap) when the OS allows it, and it is wiped when dropped
+pub struct SecretKey {
+    bytes: Box<[u8; 32]>,
+    lock: Option<region::LockGuard>,
+}
 
-    //pad or truncate the key if necessary
-    let key: [u8; 32] = key_derivation(&password, &salt)?;
-    let iv: Vec<u8> = get_iv(12); //initial vector (iv), also called a nonce
-    let mut cipher: AesGcm<'_> = AesGcm::new(key_size, &key, &iv, &[]);
+impl SecretKey {
+    fn new() -> SecretKey {
+        let bytes: Box<[u8; 32]> = Box::new([0u8; 32]);
+        let lock: Option<region::LockGuard> = region::lock(bytes.as_ptr(), bytes.len()).ok();
+        SecretKey { bytes, lock }
+    }
 
-    //create a vec of data.len 0's. This is where the encrypted data will be saved.
-    //the encryption is performed in-place, so this vector of 0's will be converted
-    //to the encrypted data
-    let mut encrypted: Vec<u8> = std::iter::repeat(0).take(data.len()).collect();
+    pub fn bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+}
 
-    //create a vec of 16 0's. This is for the mac. This library calls it a "tag", but it's really
-    // the mac address. This vector will be modified in place, just like the "encrypted" vector
-    // above
-    let mut mac: Vec<u8> = std::iter::repeat(0).take(16).collect();
+impl PartialEq for SecretKey {
+    /// Constant time comparison
+    fn eq(&self, other: &SecretKey) -> bool {
+        self.bytes
+            .iter()
+            .zip(other.bytes.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+}
 
-    //encrypt data, put it into "encrypted"
-    cipher.encrypt(data, &mut encrypted, &mut mac[..]);
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+        // the memory is unlocked after it was wiped
+        self.lock.take();
+    }
+}
 
-    //create the output string that contains the nonce, cipher text, and mac
-    let hex_iv = hex::encode(iv);
-    let hex_cipher = hex::encode(encrypted);
-    let hex_mac = hex::encode(mac);
-    let output = format!("{}/{}/{}", hex_iv, hex_cipher, hex_mac);
+/// Derives the key used to encrypt a vault from its master password
+pub fn derive_key(
+    password: &[u8],
+    salt: &[u8; 32],
+    kdf: &KdfParams
+) -> Result<SecretKey, VaultError> {
+    let mut output_key: SecretKey = SecretKey::new();
+
+    let params: Params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))?;
+    let argon2: Argon2 = Argon2::new(kdf.algorithm.argon2_algorithm(), Version::V0x13, params);
+    argon2.hash_password_into(password, salt, &mut output_key.bytes[..])?;
+
+    Ok(output_key)
+}
+
+/// Returns the header of a vault file, or None if it's a legacy (v0) file, which has no header
+pub fn read_header(data: &[u8]) -> Result<Option<VaultHeader>, VaultError> {
+    if !data.starts_with(MAGIC) {
+        return Ok(None);
+    }
+    let (header, _) = VaultHeader::parse(data)?;
+    Ok(Some(header))
+}
+
+///encrypt "data" using "key", with the given cipher suite.
+/// key must have been derived with salt and kdf, which are saved in the header
+/// Output is a VaultHeader followed by the cipher text and the mac
+pub fn encrypt(
+    data: &[u8],
+    key: &SecretKey,
+    salt: &[u8; 32],
+    cipher: CipherSuite,
+    kdf: &KdfParams
+) -> Result<Vec<u8>, VaultError> {
+    let header = VaultHeader {
+        version: FORMAT_VERSION,
+        cipher,
+        kdf: *kdf,
+        salt: *salt,
+        nonce: random::nonce(cipher.cipher().nonce_len()), //initial vector (iv), also called a nonce
+    };
+    let mut output: Vec<u8> = header.to_bytes();
+
+    // the header is passed as the aad, so it can't be tampered with without breaking the mac
+    let encrypted: Vec<u8> = cipher.cipher().encrypt(key.bytes(), &header.nonce, &output, data)?;
+    output.extend_from_slice(&encrypted);
 
     Ok(output)
 }
 
+/// Return type of split_iv_data_mac: (iv, data, mac)
+type IvDataMac = (Vec<u8>, Vec<u8>, Vec<u8>);
+
 /// orig must be a string of the form [hexNonce]/[hexCipherText]/[hexMac]. This
-/// is the data returned from encrypt(). This function splits the data, removes
+/// is the data returned from encrypt() before vault headers were added (v0). This function splits the data, removes
 /// the hex encoding, and returns each as a list of bytes.
-fn split_iv_data_mac(orig: &str) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), Box<dyn Error>> {
-    let split: Vec<&str> = orig.split('/').into_iter().collect();
+fn split_iv_data_mac(orig: &str) -> Result<IvDataMac, VaultError> {
+    let split: Vec<&str> = orig.split('/').collect();
 
     if split.len() != 3 {
-        return Err(Box::new(std::io::Error::from(ErrorKind::Other)));
+        return Err(VaultError::CorruptFile("unknown file format"));
     }
-    let iv_res = hex::decode(split[0]);
-    if iv_res.is_err() {
-        return Err(Box::new(std::io::Error::from(ErrorKind::Other)));
+    let iv = match hex::decode(split[0]) {
+        Ok(iv) => iv,
+        Err(_) => {
+            return Err(VaultError::CorruptFile("invalid hex encoding"));
+        }
+    };
+
+    let data = match hex::decode(split[1]) {
+        Ok(data) => data,
+        Err(_) => {
+            return Err(VaultError::CorruptFile("invalid hex encoding"));
+        }
+    };
+
+    let mac = match hex::decode(split[2]) {
+        Ok(mac) => mac,
+        Err(_) => {
+            return Err(VaultError::CorruptFile("invalid hex encoding"));
+        }
+    };
+
+    Ok((iv, data, mac))
+}
+
+/// True if data was not written in the current vault format, or was encrypted with a cipher
+/// suite that is only kept for compatibility, so it should be encrypted again
+pub fn needs_upgrade(data: &[u8]) -> bool {
+    if !data.starts_with(MAGIC) {
+        return true;
     }
-    let iv = iv_res.unwrap();
+    match VaultHeader::parse(data) {
+        Ok((header, _)) => header.cipher == CipherSuite::Aes128Gcm,
+        Err(_) => true,
+    }
+}
 
-    let data_res = hex::decode(split[1]);
-    if data_res.is_err() {
-        return Err(Box::new(std::io::Error::from(ErrorKind::Other)));
+///Decryption of a vault file returned by encrypt().
+/// Files that start with MAGIC have a header, and key must be derived with the salt and Argon2
+/// parameters saved in it. Otherwise, the file is a legacy (v0) string that contains the iv/nonce,
+/// data, and mac values, hex encoded and separated by "/" i.e. [hex(iv)/hex(data)/hex(mac)]; those
+/// files are decrypted with AES-GCM 128, and key must be derived with the collection's salt and
+/// the default Argon2 parameters
+pub fn decrypt(data: &[u8], key: &SecretKey) -> Result<String, VaultError> {
+    if data.starts_with(MAGIC) {
+        let (header, header_len) = VaultHeader::parse(data)?;
+        if data.len() < header_len + MAC_LEN {
+            return Err(VaultError::CorruptFile("the encrypted data is incomplete"));
+        }
+        let decrypted: Vec<u8> = header.cipher
+            .cipher()
+            .decrypt(key.bytes(), &header.nonce, &data[..header_len], &data[header_len..])?;
+        return utf8(decrypted);
     }
-    let data = data_res.unwrap();
 
-    let mac_res = hex::decode(split[2]);
-    if mac_res.is_err() {
-        return Err(Box::new(std::io::Error::from(ErrorKind::Other)));
+    let iv_data_mac: &str = match std::str::from_utf8(data) {
+        Ok(s) => s,
+        Err(_) => {
+            return Err(VaultError::CorruptFile("unknown file format"));
+        }
+    };
+    let (iv, mut data, mac) = split_iv_data_mac(iv_data_mac)?;
+    let cipher: &dyn Cipher = CipherSuite::Aes128Gcm.cipher();
+    if iv.len() != cipher.nonce_len() || mac.len() != MAC_LEN {
+        return Err(VaultError::CorruptFile("invalid nonce or mac length"));
     }
-    let mac = mac_res.unwrap();
 
-    Ok((iv, data, mac))
+    // v0 files don't use the aad for verification. aad isn't encrypted anyway, so it's just specified
+    // as &[].
+    data.extend_from_slice(&mac);
+    let decrypted: Vec<u8> = cipher.decrypt(key.bytes(), &iv, &[], &data)?;
+    utf8(decrypted)
 }
 
-///Decryption using AES-GCM 128
-///iv_data_mac is a string that contains the iv/nonce, data, and mac values. All these values
-/// must be hex encoded, and separated by "/" i.e. [hex(iv)/hex(data)/hex(mac)]. This function decodes
-/// the values. key (or password) is the raw (not hex encoded) password
-pub fn decrypt(
-    iv_data_mac: &str,
-    password: &[u8],
-    salt: &[u8; 32]
-) -> Result<String, Box<dyn Error>> {
-    let (iv, data, mac) = split_iv_data_mac(iv_data_mac)?;
-    let key: [u8; 32] = match key_derivation(&password, &salt) {
-        Ok(k) => k,
-        _ => {
-            return Err(Box::new(std::io::Error::from(ErrorKind::Other)));
-        }
+fn utf8(decrypted: Vec<u8>) -> Result<String, VaultError> {
+    match String::from_utf8(decrypted) {
+        Ok(s) => Ok(s),
+        Err(_) => Err(VaultError::CorruptFile("the decrypted data is not valid text")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Session;
+    use std::collections::HashSet;
+
+    /// Far weaker than any real vault, so the tests run fast
+    const TEST_KDF: KdfParams = KdfParams {
+        algorithm: KdfAlgorithm::Argon2id,
+        m_cost: 8,
+        t_cost: 1,
+        p_cost: 1,
     };
 
-    let key_size: crypto::aes::KeySize = crypto::aes::KeySize::KeySize128;
+    const SUITES: [CipherSuite; 3] = [
+        CipherSuite::Aes128Gcm,
+        CipherSuite::Aes256Gcm,
+        CipherSuite::XChaCha20Poly1305,
+    ];
 
-    // I don't use the aad for verification. aad isn't encrypted anyway, so it's just specified
-    // as &[].
-    let mut decipher: AesGcm<'_> = AesGcm::new(key_size, &key, &iv, &[]);
+    #[test]
+    fn nonces_never_repeat_across_saves() {
+        for cipher in [CipherSuite::Aes256Gcm, CipherSuite::XChaCha20Poly1305] {
+            let session: Session = Session::new(b"password", cipher, &TEST_KDF).unwrap();
+            let mut nonces: HashSet<Vec<u8>> = HashSet::new();
+            for _ in 0..2000 {
+                let data: Vec<u8> = session.encrypt(b"[]").unwrap();
+                let header: VaultHeader = read_header(&data).unwrap().unwrap();
+                assert_eq!(header.nonce.len(), cipher.cipher().nonce_len());
+                assert!(nonces.insert(header.nonce), "a nonce was reused with {}", cipher);
+            }
+        }
+    }
 
-    // create a list where the decoded data will be saved. dst is transformed in place. It must be exactly the same
-    // size as the encrypted data
-    let mut dst: Vec<u8> = std::iter::repeat(0).take(data.len()).collect();
-    let result: bool = decipher.decrypt(&data, &mut dst, &mac);
+    #[test]
+    fn every_cipher_suite_round_trips() {
+        let salt: [u8; 32] = random::salt();
+        let key: SecretKey = derive_key(b"password", &salt, &TEST_KDF).unwrap();
+        let wrong_key: SecretKey = derive_key(b"wrong password", &salt, &TEST_KDF).unwrap();
+        for cipher in SUITES {
+            let data: Vec<u8> = encrypt(b"some accounts", &key, &salt, cipher, &TEST_KDF).unwrap();
+            let header: VaultHeader = read_header(&data).unwrap().unwrap();
+            assert_eq!(header.cipher, cipher);
+            assert_eq!(header.salt, salt);
+            assert_eq!(header.kdf, TEST_KDF);
+            assert_eq!(decrypt(&data, &key).unwrap(), "some accounts");
+            assert!(matches!(decrypt(&data, &wrong_key), Err(VaultError::WrongPassword)));
+        }
+    }
+
+    /// An accounts file saved by the first version, with its rust-crypto encrypt (AES-128-GCM,
+    /// Argon2's default parameters and the collection's salt). It holds LEGACY_DATA, encrypted
+    /// with LEGACY_PASSWORD and LEGACY_SALT
+    const LEGACY_FILE: &[u8] =
+        b"f68f3f1bdb47a8fdf6c67894/018ff8d66b70d1b495948cab9528a4ed249e91/c19f68dbcdbd28bd90230b266257d90c";
+    const LEGACY_DATA: &str = r#"[{"name":"legacy"}]"#;
+    const LEGACY_PASSWORD: &[u8] = b"correct horse battery staple";
+    const LEGACY_SALT: [u8; 32] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
+        27, 28, 29, 30, 31,
+    ];
+
+    #[test]
+    fn legacy_files_can_still_be_read() {
+        assert!(read_header(LEGACY_FILE).unwrap().is_none());
+        let (session, decrypted) = Session::unlock(
+            LEGACY_FILE,
+            LEGACY_PASSWORD,
+            Some(&LEGACY_SALT),
+            CipherSuite::default()
+        ).unwrap();
+        assert_eq!(decrypted, LEGACY_DATA);
+        // and they are saved again with a header
+        assert!(read_header(&session.encrypt(b"[]").unwrap()).unwrap().is_some());
+
+        let wrong = Session::unlock(LEGACY_FILE, b"wrong password", Some(&LEGACY_SALT), CipherSuite::default());
+        assert!(matches!(wrong, Err(VaultError::WrongPassword)));
+        let no_salt = Session::unlock(LEGACY_FILE, LEGACY_PASSWORD, None, CipherSuite::default());
+        assert!(matches!(no_salt, Err(VaultError::MissingSalt)));
+    }
 
-    if !result {
-        return Err(Box::new(std::io::Error::from(ErrorKind::PermissionDenied)));
+    #[test]
+    fn a_modified_header_is_rejected() {
+        let salt: [u8; 32] = random::salt();
+        let key: SecretKey = derive_key(b"password", &salt, &TEST_KDF).unwrap();
+        let mut data: Vec<u8> = encrypt(b"[]", &key, &salt, CipherSuite::default(), &TEST_KDF).unwrap();
+        // the header is authenticated, so changing the iterations breaks the mac
+        data[MAGIC.len() + 7] ^= 1;
+        assert!(matches!(decrypt(&data, &key), Err(VaultError::WrongPassword)));
     }
 
-    Ok(String::from_utf8(dst)?)
+    #[test]
+    fn costly_key_derivation_parameters_are_rejected_before_deriving() {
+        let salt: [u8; 32] = random::salt();
+        let key: SecretKey = derive_key(b"password", &salt, &TEST_KDF).unwrap();
+        let data: Vec<u8> = encrypt(b"[]", &key, &salt, CipherSuite::default(), &TEST_KDF).unwrap();
+        // m, t and p follow the version, cipher and algorithm bytes
+        for offset in [3, 7, 11] {
+            let mut modified: Vec<u8> = data.clone();
+            let start: usize = MAGIC.len() + offset;
+            modified[start..start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            assert!(matches!(read_header(&modified), Err(VaultError::CorruptFile(_))));
+            let unlocked = Session::unlock(&modified, b"password", None, CipherSuite::default());
+            assert!(matches!(unlocked, Err(VaultError::CorruptFile(_))));
+        }
+
+        let costly = KdfParams { m_cost: MAX_M_COST + 1, ..TEST_KDF };
+        assert!(matches!(costly.check(), Err(VaultError::KdfOverLimit(_))));
+        assert!(TEST_KDF.check().is_ok());
+    }
 }
@@ -0,0 +1,100 @@
+use inquire::InquireError;
+use thiserror::Error;
+
+/// Errors returned when reading, decrypting or saving collections and accounts files
+#[derive(Debug, Error)]
+pub enum VaultError {
+    /// The mac didn't match: the password is wrong (or the file was modified)
+    #[error("incorrect password")]
+    WrongPassword,
+    #[error("the file is corrupt: {0}")]
+    CorruptFile(&'static str),
+    #[error("this file was saved in format version {0}, which this version doesn't support")]
+    UnsupportedVersion(u8),
+    /// A legacy (v0) accounts file can't be decrypted without the salt of its collection
+    #[error("this accounts file was created by an older version, and its salt is missing")]
+    MissingSalt,
+    #[error("encryption failed")]
+    Encryption,
+    #[error("key derivation failed: {0}")]
+    Kdf(argon2::Error),
+    #[error("the key derivation would use {0}")]
+    KdfOverLimit(&'static str),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("could not read the saved data: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error(transparent)]
+    Prompt(#[from] InquireError),
+}
+
+impl From<argon2::Error> for VaultError {
+    fn from(e: argon2::Error) -> Self {
+        VaultError::Kdf(e)
+    }
+}
+
+/// Errors returned when the password or passphrase generator options can't produce one
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    #[error("at least one character class must be selected")]
+    NoCharacterClasses,
+    #[error("the password must be at least {0} characters long to include every selected class")]
+    TooShort(usize),
+    #[error("a passphrase needs at least one word")]
+    NoWords,
+}
+
+/// Errors returned when a one-time password secret or otpauth:// URI can't be used
+#[derive(Debug, Error)]
+pub enum OtpError {
+    #[error("the secret is not valid base32")]
+    InvalidSecret,
+    #[error("invalid otpauth:// URI: {0}")]
+    InvalidUri(&'static str),
+    #[error("it isn't an otpauth://{0}/ URI")]
+    WrongKind(&'static str),
+    #[error("unsupported algorithm {0}, it must be SHA1, SHA256 or SHA512")]
+    UnsupportedAlgorithm(String),
+    #[error("codes must have between 6 and 8 digits")]
+    InvalidDigits,
+}
+
+/// Errors returned when a custom field's value doesn't match its type
+#[derive(Debug, Error)]
+pub enum FieldError {
+    #[error("the field needs a name")]
+    EmptyName,
+    #[error("expected NAME=VALUE or NAME:TYPE=VALUE")]
+    MissingValue,
+    #[error("unknown field type {0}, it must be text, hidden, url, number or date")]
+    UnknownKind(String),
+    #[error("not a URL, it must look like https://example.com")]
+    InvalidUrl,
+    #[error("not a number")]
+    InvalidNumber,
+    #[error("not a date, it must be YYYY-MM-DD")]
+    InvalidDate,
+}
+
+/// Errors returned when copying to or clearing the clipboard
+#[derive(Debug, Error)]
+pub enum ClipboardError {
+    #[error("no clipboard is available: {0}")]
+    Unavailable(String),
+    #[error("could not read the clipboard: {0}")]
+    Read(String),
+    #[error("could not copy to the clipboard: {0}")]
+    Write(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Errors returned when an account's URL can't be used for matching
+#[derive(Debug, Error)]
+pub enum UrlError {
+    #[error("not a valid URL: {0}")]
+    Invalid(#[from] url::ParseError),
+    #[error("the URL has no host")]
+    NoHost,
+}
@@ -0,0 +1,243 @@
+use inquire::validator::Validation;
+use inquire::{ InquireError, Password, PasswordDisplayMode, Select, Text };
+use serde::{ Deserialize, Serialize };
+use std::fmt::{ self, Display };
+use zeroize::Zeroize;
+
+use crate::error::FieldError;
+
+/// What a custom field holds. Values are always stored as text, the kind decides how they are
+/// checked and shown
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    Text,
+    /// Masked like the password, for PINs, security answers, API keys...
+    Hidden,
+    Url,
+    Number,
+    /// YYYY-MM-DD
+    Date,
+}
+
+impl FieldKind {
+    const ALL: [FieldKind; 5] = [
+        FieldKind::Text,
+        FieldKind::Hidden,
+        FieldKind::Url,
+        FieldKind::Number,
+        FieldKind::Date,
+    ];
+
+    pub fn parse(name: &str) -> Result<FieldKind, FieldError> {
+        FieldKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| FieldError::UnknownKind(name.to_owned()))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            FieldKind::Text => "text",
+            FieldKind::Hidden => "hidden",
+            FieldKind::Url => "url",
+            FieldKind::Number => "number",
+            FieldKind::Date => "date",
+        }
+    }
+
+    /// Checks that value can be stored in a field of this kind
+    pub fn check(&self, value: &str) -> Result<(), FieldError> {
+        match self {
+            FieldKind::Text | FieldKind::Hidden => Ok(()),
+            FieldKind::Url => {
+                match value.split_once("://") {
+                    Some((scheme, rest)) if
+                        !scheme.is_empty() &&
+                        scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) &&
+                        !rest.is_empty()
+                    => Ok(()),
+                    _ => Err(FieldError::InvalidUrl),
+                }
+            }
+            FieldKind::Number => {
+                match value.trim().parse::<f64>() {
+                    Ok(number) if number.is_finite() => Ok(()),
+                    _ => Err(FieldError::InvalidNumber),
+                }
+            }
+            FieldKind::Date => if is_date(value) { Ok(()) } else { Err(FieldError::InvalidDate) }
+        }
+    }
+}
+
+impl Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// True if value is a valid YYYY-MM-DD date
+fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return false;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse::<u32>(), month.parse::<u32>(), day.parse::<u32>()) else {
+        return false;
+    };
+    let leap: bool = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days: u32 = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => {
+            return false;
+        }
+    };
+    (1..=days).contains(&day)
+}
+
+/// A user defined field of an account, like a security question, a PIN or an account number
+#[derive(Serialize, Deserialize, Debug, Clone, Zeroize)]
+pub struct CustomField {
+    pub name: String,
+    #[zeroize(skip)]
+    pub kind: FieldKind,
+    value: String,
+}
+
+impl CustomField {
+    pub fn new(name: String, kind: FieldKind, value: String) -> Result<CustomField, FieldError> {
+        if name.trim().is_empty() {
+            return Err(FieldError::EmptyName);
+        }
+        kind.check(&value)?;
+        Ok(CustomField { name, kind, value })
+    }
+
+    /// Reads NAME=VALUE or NAME:KIND=VALUE, as given on the command line. The kind is text by
+    /// default
+    pub fn parse(input: &str) -> Result<CustomField, FieldError> {
+        let (key, value) = input.split_once('=').ok_or(FieldError::MissingValue)?;
+        let (name, kind) = match key.rsplit_once(':') {
+            Some((name, kind)) => (name, FieldKind::parse(kind)?),
+            None => (key, FieldKind::Text),
+        };
+        CustomField::new(name.to_owned(), kind, value.to_owned())
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The value, or asterisks if the field is hidden and show_hidden is false
+    pub fn display_value(&self, show_hidden: bool) -> String {
+        if self.kind == FieldKind::Hidden && !show_hidden {
+            "*".repeat(self.value.len())
+        } else {
+            self.value.clone()
+        }
+    }
+}
+
+enum FieldAction {
+    Done,
+    Add,
+    Edit(usize, String),
+    MoveUp(usize, String),
+    Remove(usize, String),
+}
+
+impl Display for FieldAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldAction::Done => write!(f, "Done"),
+            FieldAction::Add => write!(f, "Add a field"),
+            FieldAction::Edit(_, name) => write!(f, "Edit \"{}\"", name),
+            FieldAction::MoveUp(_, name) => write!(f, "Move \"{}\" up", name),
+            FieldAction::Remove(_, name) => write!(f, "Remove \"{}\"", name),
+        }
+    }
+}
+
+/// Asks for the name, kind and value of a field. current is the field being edited, if any
+fn prompt_field(current: Option<&CustomField>) -> Result<CustomField, InquireError> {
+    let name: String = Text::new("field name: ")
+        .with_initial_value(current.map_or("", |f| f.name.as_str()))
+        .with_validator(|name: &str| {
+            Ok(if name.trim().is_empty() {
+                Validation::Invalid(FieldError::EmptyName.into())
+            } else {
+                Validation::Valid
+            })
+        })
+        .prompt()?;
+    let kinds: Vec<FieldKind> = FieldKind::ALL.to_vec();
+    let starting: usize = current.map_or(0, |f| {
+        kinds
+            .iter()
+            .position(|kind| *kind == f.kind)
+            .unwrap_or(0)
+    });
+    let kind: FieldKind = Select::new("field type: ", kinds).with_starting_cursor(starting).prompt()?;
+
+    let value: String = if kind == FieldKind::Hidden {
+        let mut prompt: Password = Password::new("field value: ")
+            .without_confirmation()
+            .with_display_mode(PasswordDisplayMode::Masked);
+        if current.is_some() {
+            prompt = prompt.with_help_message("leave it empty to keep the current value");
+        }
+        let value: String = prompt.prompt()?;
+        match current {
+            Some(current) if value.is_empty() => current.value.clone(),
+            _ => value,
+        }
+    } else {
+        Text::new("field value: ")
+            .with_initial_value(current.map_or("", |f| f.value.as_str()))
+            .with_validator(move |value: &str| {
+                Ok(match kind.check(value) {
+                    Ok(_) => Validation::Valid,
+                    Err(e) => Validation::Invalid(e.into()),
+                })
+            })
+            .prompt()?
+    };
+
+    CustomField::new(name, kind, value).map_err(|e| InquireError::Custom(Box::new(e)))
+}
+
+/// Lets the user add, edit, reorder and remove custom fields, until they choose "Done"
+pub fn prompt_fields(current: &[CustomField]) -> Result<Vec<CustomField>, InquireError> {
+    let mut fields: Vec<CustomField> = current.to_vec();
+    loop {
+        let mut actions: Vec<FieldAction> = vec![FieldAction::Done, FieldAction::Add];
+        for (index, field) in fields.iter().enumerate() {
+            actions.push(FieldAction::Edit(index, field.name.clone()));
+            if index > 0 {
+                actions.push(FieldAction::MoveUp(index, field.name.clone()));
+            }
+            actions.push(FieldAction::Remove(index, field.name.clone()));
+        }
+
+        match Select::new("custom fields: ", actions).prompt()? {
+            FieldAction::Done => {
+                return Ok(fields);
+            }
+            FieldAction::Add => fields.push(prompt_field(None)?),
+            FieldAction::Edit(i, _) => {
+                let mut edited: CustomField = prompt_field(Some(&fields[i]))?;
+                std::mem::swap(&mut fields[i], &mut edited);
+                edited.zeroize();
+            }
+            FieldAction::MoveUp(i, _) => fields.swap(i - 1, i),
+            FieldAction::Remove(i, _) => fields.remove(i).zeroize(),
+        }
+    }
+}
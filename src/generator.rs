@@ -0,0 +1,149 @@
+use inquire::{ Confirm, CustomType, InquireError, MultiSelect };
+use std::fmt::{ self, Display };
+use zeroize::Zeroize;
+
+use crate::error::GeneratorError;
+use crate::random;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.<>/?~";
+/// Characters that are easy to mistake for one another when reading or typing a password
+const AMBIGUOUS: &str = "0Oo1lI|";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Lowercase,
+    Uppercase,
+    Digits,
+    Symbols,
+}
+
+impl CharClass {
+    const ALL: [CharClass; 4] = [
+        CharClass::Lowercase,
+        CharClass::Uppercase,
+        CharClass::Digits,
+        CharClass::Symbols,
+    ];
+
+    fn characters(&self, exclude_ambiguous: bool) -> Vec<char> {
+        let characters: &str = match self {
+            CharClass::Lowercase => LOWERCASE,
+            CharClass::Uppercase => UPPERCASE,
+            CharClass::Digits => DIGITS,
+            CharClass::Symbols => SYMBOLS,
+        };
+        characters
+            .chars()
+            .filter(|c| !(exclude_ambiguous && AMBIGUOUS.contains(*c)))
+            .collect()
+    }
+}
+
+impl Display for CharClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CharClass::Lowercase => write!(f, "Lowercase letters (a-z)"),
+            CharClass::Uppercase => write!(f, "Uppercase letters (A-Z)"),
+            CharClass::Digits => write!(f, "Digits (0-9)"),
+            CharClass::Symbols => write!(f, "Symbols ({})", SYMBOLS),
+        }
+    }
+}
+
+/// What a generated password is made of. Every selected class appears at least once
+#[derive(Clone, PartialEq, Eq)]
+pub struct PasswordOptions {
+    pub length: usize,
+    pub classes: Vec<CharClass>,
+    pub exclude_ambiguous: bool,
+}
+
+impl Default for PasswordOptions {
+    fn default() -> Self {
+        PasswordOptions {
+            length: 20,
+            classes: CharClass::ALL.to_vec(),
+            exclude_ambiguous: false,
+        }
+    }
+}
+
+impl PasswordOptions {
+    /// Checks that a password can be generated with these options
+    pub fn check(&self) -> Result<(), GeneratorError> {
+        if self.classes.is_empty() {
+            return Err(GeneratorError::NoCharacterClasses);
+        }
+        if self.length < self.classes.len() {
+            return Err(GeneratorError::TooShort(self.classes.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Generates a random password with the OS CSPRNG. One character of each selected class is
+/// picked first, the rest are picked from all of them together, and then they are shuffled, so
+/// the guaranteed characters can be anywhere in the password
+pub fn generate(options: &PasswordOptions) -> Result<String, GeneratorError> {
+    options.check()?;
+
+    let classes: Vec<Vec<char>> = options.classes
+        .iter()
+        .map(|class| class.characters(options.exclude_ambiguous))
+        .collect();
+    let all: Vec<char> = classes.concat();
+
+    let mut chars: Vec<char> = classes
+        .iter()
+        .map(|class| class[random::below(class.len())])
+        .collect();
+    while chars.len() < options.length {
+        chars.push(all[random::below(all.len())]);
+    }
+    random::shuffle(&mut chars);
+
+    let password: String = chars.iter().collect();
+    chars.zeroize();
+    Ok(password)
+}
+
+/// Asks for the generator options, and generates passwords until the user accepts one
+pub fn prompt_generated_password() -> Result<String, InquireError> {
+    let options: PasswordOptions = loop {
+        let default: PasswordOptions = PasswordOptions::default();
+        let length: usize = CustomType::new("Length: ").with_default(default.length).prompt()?;
+        let classes: Vec<CharClass> = MultiSelect::new("Characters: ", CharClass::ALL.to_vec())
+            .with_default(&[0, 1, 2, 3])
+            .prompt()?;
+        let exclude_ambiguous: bool = Confirm::new(
+            &format!("Exclude ambiguous characters ({})?", AMBIGUOUS)
+        )
+            .with_default(default.exclude_ambiguous)
+            .prompt()?;
+
+        let options = PasswordOptions { length, classes, exclude_ambiguous };
+        match options.check() {
+            Ok(_) => {
+                break options;
+            }
+            Err(e) => println!("Invalid options: {}. Try again", e),
+        }
+    };
+
+    loop {
+        let mut password: String = match generate(&options) {
+            Ok(password) => password,
+            Err(e) => {
+                return Err(InquireError::Custom(Box::new(e)));
+            }
+        };
+        println!("Generated password: {}", password);
+        if Confirm::new("Use this password?").with_default(true).prompt()? {
+            return Ok(password);
+        }
+        password.zeroize();
+    }
+}
@@ -1,15 +1,41 @@
+use clap::Parser;
+use cli::Cli;
 use manager::*;
 
 mod accounts;
+mod audit;
+mod backups;
+mod breaches;
+mod cli;
+mod clipboard;
 mod collections;
+mod config;
 mod cryptography;
+mod error;
+mod fields;
+mod generator;
 mod manager;
+mod otp;
+mod passphrase;
+mod random;
+mod search;
+mod session;
+mod strength;
+mod urls;
 mod utils;
 
 fn main() {
-    let mut manager = Manager::new();
-    match manager.display_menu() {
-        _ => {}
+    let cli: Cli = Cli::parse();
+
+    if let Some(command) = cli.command {
+        if let Err(e) = cli::run(command, &cli.password) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
     }
+
+    let mut manager = Manager::new();
+    _ = manager.display_menu();
     println!("Good Bye! :)");
 }
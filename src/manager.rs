@@ -1,22 +1,39 @@
 use crate::accounts::*;
+use crate::audit::audit;
+use crate::backups::*;
+use crate::clipboard::{ copy_sequence, copy_with_countdown, Clipboard, SystemClipboard };
 use crate::collections::*;
+use crate::config::*;
+use crate::cryptography::KdfParams;
+use crate::error::{ ClipboardError, VaultError };
+use crate::fields::CustomField;
+use crate::search::{ search, SearchHit };
+use crate::session::Session;
 use crate::utils::*;
-use inquire::{ Confirm, InquireError, Select };
+use inquire::{ Confirm, InquireError, Password, PasswordDisplayMode, Select, Text };
+use std::collections::HashMap;
 use std::error::Error;
-use std::usize;
 use std::{ fmt, fs };
 use zeroize::Zeroize;
 use Action::*;
 
-const COLLECTIONS_FILE_PATH: &str = "./collections.json";
+pub const COLLECTIONS_FILE_PATH: &str = "./collections.json";
+pub const CONFIG_FILE_PATH: &str = "./config.json";
 
 enum Action {
     CollectionSelection(usize, String),
     AccountSelection(usize, String),
     LoadAccounts,
     NewCollection,
+    SearchAllCollections,
     NewAccount,
+    SearchAccounts,
+    FilterByTag,
+    ClearTagFilter(String),
     EditCollection,
+    RekeyCollection,
+    RestoreBackup,
+    AuditAccounts,
     EditAccount,
     DeleteCollection,
     DeleteAccount,
@@ -24,6 +41,12 @@ enum Action {
     GoBackToAccounts,
     TogglePasswordView,
     CopyToClipboard,
+    CopyUsername,
+    CopyEmail,
+    TypeAhead(String),
+    CopyField,
+    CopyTotpCode,
+    NextHotpCode,
     Exit,
 }
 
@@ -34,8 +57,15 @@ impl fmt::Display for Action {
             AccountSelection(_, a) => write!(f, "{}", a),
             LoadAccounts => write!(f, "Load accounts"),
             NewCollection => write!(f, "Add new collection"),
+            SearchAllCollections => write!(f, "Search all collections"),
             NewAccount => write!(f, "Add new account"),
+            SearchAccounts => write!(f, "Search accounts"),
+            FilterByTag => write!(f, "Filter by tag"),
+            ClearTagFilter(tag) => write!(f, "Clear tag filter \"{}\"", tag),
             EditCollection => write!(f, "Edit collection"),
+            RekeyCollection => write!(f, "Change key derivation strength"),
+            RestoreBackup => write!(f, "Restore a backup"),
+            AuditAccounts => write!(f, "Audit passwords"),
             EditAccount => write!(f, "Edit account"),
             DeleteCollection => write!(f, "Delete collection"),
             DeleteAccount => write!(f, "Delete account"),
@@ -43,35 +73,51 @@ impl fmt::Display for Action {
             GoBackToAccounts => write!(f, "Go Back"),
             TogglePasswordView => write!(f, "Show/hide password"),
             CopyToClipboard => write!(f, "Copy password to clipboard"),
+            CopyUsername => write!(f, "Copy username to clipboard"),
+            CopyEmail => write!(f, "Copy email to clipboard"),
+            TypeAhead(login) => write!(f, "Copy {} and then password, one per key press", login),
+            CopyField => write!(f, "Copy a custom field to clipboard"),
+            CopyTotpCode => write!(f, "Copy current TOTP code to clipboard"),
+            NextHotpCode => write!(f, "Generate next HOTP code"),
             Exit => write!(f, "Exit"),
         }
     }
 }
 
 pub struct Manager {
+    config: Config,
     collections: Vec<Collection>,
     accounts: Option<Vec<Account>>,
     selected_coll_index: Option<usize>,
     selected_acc_index: Option<usize>,
-    password: String,
-    salt: [u8; 32],
+    session: Option<Session>,
+    /// Sessions of collections that were unlocked and then left, by accounts file path, so they
+    /// can be opened and searched again without the master password until the program exits
+    sessions: HashMap<String, Session>,
     update_collections: bool,
     update_accounts: bool,
     show_password: bool,
+    /// Only the accounts with this tag are listed
+    tag_filter: Option<String>,
+    /// Opened the first time something is copied
+    clipboard: Option<Box<dyn Clipboard>>,
 }
 
 impl Manager {
     pub fn new() -> Self {
         Self {
+            config: Config::default(),
             collections: vec![],
             accounts: None,
             selected_coll_index: None,
             selected_acc_index: None,
-            password: String::new(),
-            salt: [0u8; 32],
+            session: None,
+            sessions: HashMap::new(),
             update_collections: false,
             update_accounts: false,
             show_password: false,
+            tag_filter: None,
+            clipboard: None,
         }
     }
 
@@ -83,26 +129,78 @@ impl Manager {
 
             clear_terminal_screen();
 
-            if self.selected_coll_index.is_some() {
+            if let Some(coll_index) = self.selected_coll_index {
                 self.print_collection_info();
                 if self.selected_acc_index.is_some() {
                     self.print_account_info();
                     options.push(TogglePasswordView);
                     options.push(CopyToClipboard);
+                    let account: &Account = self.selected_account();
+                    if !account.username().is_empty() {
+                        options.push(CopyUsername);
+                    }
+                    if !account.email().is_empty() {
+                        options.push(CopyEmail);
+                    }
+                    // sites that log in with the email usually don't ask for a username
+                    if !account.username().is_empty() {
+                        options.push(TypeAhead(String::from("username")));
+                    } else if !account.email().is_empty() {
+                        options.push(TypeAhead(String::from("email")));
+                    }
+                    if !self.selected_account().fields().is_empty() {
+                        options.push(CopyField);
+                    }
+                    if self.selected_account().totp().is_some() {
+                        options.push(CopyTotpCode);
+                    }
+                    if self.selected_account().hotp().is_some() {
+                        options.push(NextHotpCode);
+                    }
                     options.push(EditAccount);
                     options.push(DeleteAccount);
                     options.push(GoBackToAccounts);
                 } else {
-                    if self.accounts.is_none() {
-                        options.push(LoadAccounts);
-                    } else {
-                        self.accounts
-                            .as_ref()
-                            .unwrap()
-                            .iter()
-                            .enumerate()
-                            .for_each(|(i, a)| options.push(AccountSelection(i, a.name.clone())));
-                        options.push(NewAccount);
+                    match &self.accounts {
+                        None => {
+                            options.push(LoadAccounts);
+                            // offered before loading too, since a corrupt accounts file can't be
+                            let file_path: &str = &self.collections[coll_index].file_path;
+                            if !list_backups(file_path).is_empty() {
+                                options.push(RestoreBackup);
+                            }
+                        }
+                        Some(accounts) => {
+                            // grouped by folder, with the accounts that aren't in one first. The
+                            // sort is stable, so each folder keeps the order of its accounts
+                            let mut listed: Vec<(usize, &Account)> = accounts
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, a)| {
+                                    self.tag_filter.as_ref().is_none_or(|tag| a.has_tag(tag))
+                                })
+                                .collect();
+                            listed.sort_by_key(|(_, a)| a.folder());
+                            listed.into_iter().for_each(|(i, a)| {
+                                let label: String = match a.folder() {
+                                    Some(folder) => format!("{}/{}", folder, a.name),
+                                    None => a.name.clone(),
+                                };
+                                options.push(AccountSelection(i, label));
+                            });
+                            options.push(NewAccount);
+                            options.push(SearchAccounts);
+                            match &self.tag_filter {
+                                Some(tag) => options.push(ClearTagFilter(tag.clone())),
+                                None if accounts.iter().any(|a| !a.tags().is_empty()) => {
+                                    options.push(FilterByTag);
+                                }
+                                None => {}
+                            }
+                            options.push(AuditAccounts);
+                            options.push(RekeyCollection);
+                            options.push(RestoreBackup);
+                        }
                     }
                     options.push(EditCollection);
                     options.push(DeleteCollection);
@@ -114,6 +212,9 @@ impl Manager {
                     .enumerate()
                     .for_each(|(i, c)| options.push(CollectionSelection(i, c.name.clone())));
                 options.push(NewCollection);
+                if !self.collections.is_empty() {
+                    options.push(SearchAllCollections);
+                }
             }
 
             options.push(Exit);
@@ -134,13 +235,26 @@ impl Manager {
                 }
             };
 
-            _ = (match action {
+            match action {
                 CollectionSelection(index, _) => self.select_collection(index),
                 AccountSelection(index, _) => self.select_account(index),
                 LoadAccounts => self.load_accounts(),
                 NewCollection => self.add_collection(),
+                SearchAllCollections => self.search_all_collections(),
                 NewAccount => self.add_account(),
+                SearchAccounts => self.search_accounts(),
+                FilterByTag => self.filter_by_tag(),
+                ClearTagFilter(_) => {
+                    self.tag_filter = None;
+                    Ok(())
+                }
                 EditCollection => self.edit_collection(),
+                RekeyCollection => self.rekey_collection(),
+                RestoreBackup => self.restore_backup(),
+                AuditAccounts => {
+                    self.audit_accounts();
+                    Ok(())
+                }
                 EditAccount => self.edit_account(),
                 DeleteCollection => self.delete_collection(),
                 DeleteAccount => self.delete_account(),
@@ -151,14 +265,34 @@ impl Manager {
                     Ok(())
                 }
                 CopyToClipboard => {
-                    self.copy_to_clipboard();
+                    self.copy(self.selected_account().password().to_owned(), "password");
+                    Ok(())
+                }
+                CopyUsername => {
+                    self.copy(self.selected_account().username().to_owned(), "username");
+                    Ok(())
+                }
+                CopyEmail => {
+                    self.copy(self.selected_account().email().to_owned(), "email");
+                    Ok(())
+                }
+                TypeAhead(login) => {
+                    self.type_ahead(&login);
+                    Ok(())
+                }
+                CopyTotpCode => {
+                    if let Some(totp) = self.selected_account().totp() {
+                        self.copy(totp.now().0, "TOTP code");
+                    }
                     Ok(())
                 }
+                CopyField => self.copy_field(),
+                NextHotpCode => self.next_hotp_code(),
                 Exit => {
                     self.save_and_exit()?;
                     break;
                 }
-            })?;
+            }?;
         }
 
         Ok(())
@@ -169,19 +303,25 @@ impl Manager {
             save_collections(&self.collections, COLLECTIONS_FILE_PATH)?;
         }
 
-        if self.update_accounts && self.accounts.is_some() {
+        if
+            let (true, Some(accounts), Some(session)) = (
+                self.update_accounts,
+                &self.accounts,
+                &self.session,
+            )
+        {
             save_accounts(
-                self.accounts.as_ref().unwrap(),
+                accounts,
                 &self.collections.get(self.selected_coll_index.unwrap()).unwrap().file_path,
-                &self.password,
-                &self.salt
+                session,
+                self.config.backup_count
             )?;
+            self.update_accounts = false;
         }
 
         self.collections.zeroize();
         self.accounts.zeroize();
-        self.password.zeroize();
-        self.salt.zeroize();
+        self.session = None;
         clear_terminal_screen();
         println!("Information succesfully saved!");
         Ok(())
@@ -189,6 +329,7 @@ impl Manager {
 
     // collections actions
     fn load_collections(&mut self) -> Result<(), Box<dyn Error>> {
+        self.config = get_config(CONFIG_FILE_PATH)?;
         self.collections = get_collections(COLLECTIONS_FILE_PATH)?;
         Ok(())
     }
@@ -199,26 +340,35 @@ impl Manager {
 
     fn select_collection(&mut self, index: usize) -> Result<(), Box<dyn Error>> {
         self.selected_coll_index = Some(index);
-        self.salt = self.collections[self.selected_coll_index.unwrap()].salt;
         Ok(())
     }
 
     fn unselect_collection(&mut self) -> Result<(), Box<dyn Error>> {
-        if self.update_accounts && self.accounts.is_some() {
+        if
+            let (true, Some(accounts), Some(session)) = (
+                self.update_accounts,
+                &self.accounts,
+                &self.session,
+            )
+        {
             save_accounts(
-                self.accounts.as_ref().unwrap(),
+                accounts,
                 &self.collections.get(self.selected_coll_index.unwrap()).unwrap().file_path,
-                &self.password,
-                &self.salt
+                session,
+                self.config.backup_count
             )?;
+            self.update_accounts = false;
+        }
+        if let Some(session) = self.session.take() {
+            let file_path: &String = &self.collections[self.selected_coll_index.unwrap()].file_path;
+            self.sessions.insert(file_path.clone(), session);
         }
         self.accounts.zeroize();
         self.accounts = None;
         self.selected_acc_index = None;
         self.selected_coll_index = None;
-        self.salt.zeroize();
-        self.password.zeroize();
         self.show_password = false;
+        self.tag_filter = None;
         Ok(())
     }
 
@@ -243,10 +393,179 @@ impl Manager {
                 Ok(())
             }
             Err(InquireError::OperationCanceled) => Ok(()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    fn rekey_collection(&mut self) -> Result<(), Box<dyn Error>> {
+        let kdf: KdfParams = match prompt_kdf_params() {
+            Ok(kdf) => kdf,
+            Err(InquireError::OperationCanceled) => {
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(Box::new(e));
+            }
+        };
+
+        // the password isn't kept after unlocking, so it's needed again to derive the new key
+        let mut password: String = match prompt_master_password() {
+            Ok(p) => p,
+            Err(InquireError::OperationCanceled) => {
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(Box::new(e));
+            }
+        };
+        match self.session.as_ref().unwrap().verify(password.as_bytes()) {
+            Ok(_) => {}
+            Err(VaultError::WrongPassword) => {
+                password.zeroize();
+                println!("Incorrect password!");
+                wait_for_enter();
+                return Ok(());
+            }
+            Err(e) => {
+                password.zeroize();
+                return Err(Box::new(e));
+            }
+        }
+
+        let collection: &mut Collection = self.collections
+            .get_mut(self.selected_coll_index.unwrap())
+            .unwrap();
+        collection.rekey(kdf);
+        let session: Result<Session, VaultError> = Session::new(
+            password.as_bytes(),
+            collection.cipher,
+            &collection.kdf
+        );
+        password.zeroize();
+        self.session = Some(session?);
+
+        // the accounts file is saved right away with a new salt, and the collections file too,
+        // so the new parameters are also used if the accounts file is ever created again
+        save_accounts(
+            self.accounts.as_ref().unwrap(),
+            &collection.file_path,
+            self.session.as_ref().unwrap(),
+            self.config.backup_count
+        )?;
+        save_collections(&self.collections, COLLECTIONS_FILE_PATH)?;
+        self.update_accounts = false;
+        Ok(())
+    }
+
+    fn audit_accounts(&self) {
+        if let Some(accounts) = &self.accounts {
+            clear_terminal_screen();
+            match audit(accounts, &self.config) {
+                Ok(report) => println!("{}", report),
+                Err(e) => println!("Could not audit the passwords: {}", e),
+            }
+            wait_for_enter();
+        }
+    }
+
+    fn restore_backup(&mut self) -> Result<(), Box<dyn Error>> {
+        let collection: &Collection = &self.collections[self.selected_coll_index.unwrap()];
+        let backups: Vec<Backup> = list_backups(&collection.file_path);
+        if backups.is_empty() {
+            println!("This collection has no backups yet");
+            wait_for_enter();
+            return Ok(());
+        }
+
+        let backup: Backup = match Select::new("Choose a backup to restore:", backups).prompt() {
+            Ok(b) => b,
+            Err(InquireError::OperationCanceled) => {
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(Box::new(e));
+            }
+        };
+
+        let data: Vec<u8> = fs::read(&backup.path)?;
+        // without loaded accounts, the backup is decrypted on its own, and the session it gives
+        // replaces the one of the accounts file
+        let restored: Result<(Vec<Account>, Option<Session>), VaultError> = match
+            (&self.session, &self.accounts)
+        {
+            (Some(session), Some(current)) => {
+                get_backup_accounts(&data, session, collection.salt.as_ref()).map(|mut accounts| {
+                    keep_hotp_counters(&mut accounts, current);
+                    (accounts, None)
+                })
+            }
+            _ => {
+                let mut password: String = prompt_master_password()?;
+                let unlocked = unlock_backup(
+                    &data,
+                    &collection.file_path,
+                    password.as_bytes(),
+                    collection.salt.as_ref(),
+                    collection.cipher
+                );
+                password.zeroize();
+                unlocked.map(|(accounts, session)| (accounts, Some(session)))
+            }
+        };
+        let (mut accounts, session) = match restored {
+            Ok(r) => r,
+            Err(VaultError::Prompt(InquireError::OperationCanceled)) => {
+                return Ok(());
+            }
+            Err(VaultError::WrongPassword) if self.session.is_none() => {
+                println!(
+                    "This backup can't be restored: the password is incorrect, or the accounts file doesn't take the same one as the backup"
+                );
+                wait_for_enter();
+                return Ok(());
+            }
+            Err(e) => {
+                println!("This backup can't be restored: {}", e);
+                wait_for_enter();
+                return Ok(());
+            }
+        };
+
+        let ans: bool = match
+            Confirm::new(
+                &format!("Replace the current accounts with the {} accounts in this backup?", accounts.len())
+            )
+                .with_default(false)
+                .with_help_message(
+                    "The current accounts will be kept as the most recent backup. HOTP counters are never lowered, except for accounts deleted since the backup, whose old codes would be generated again"
+                )
+                .prompt()
+        {
+            Ok(ans) => ans,
+            Err(InquireError::OperationCanceled) => false,
             Err(e) => {
                 return Err(Box::new(e));
             }
+        };
+
+        if !ans {
+            accounts.zeroize();
+        } else {
+            self.accounts.zeroize();
+            self.accounts = Some(accounts);
+            if session.is_some() {
+                self.session = session;
+            }
+            // saved right away with the current key, so the current file becomes a backup
+            save_accounts(
+                self.accounts.as_ref().unwrap(),
+                &collection.file_path,
+                self.session.as_ref().unwrap(),
+                self.config.backup_count
+            )?;
+            self.update_accounts = false;
         }
+        Ok(())
     }
 
     fn delete_collection(&mut self) -> Result<(), Box<dyn Error>> {
@@ -266,17 +585,22 @@ impl Manager {
         };
 
         if ans {
-            match
-                fs::remove_file(
-                    &self.collections.get(self.selected_coll_index.unwrap()).unwrap().file_path
-                )
+            if
+                fs
+                    ::remove_file(
+                        &self.collections.get(self.selected_coll_index.unwrap()).unwrap().file_path
+                    )
+                    .is_err()
             {
-                Err(_) => {
-                    println!("Passwords file not found!");
-                    // Err(InquireError::Custom(Box::new(e)))
-                }
-                _ => {}
+                println!("Passwords file not found!");
+                // Err(InquireError::Custom(Box::new(e)))
             }
+            remove_backups(&self.collections.get(self.selected_coll_index.unwrap()).unwrap().file_path);
+            // the accounts of a deleted collection must not be saved when unselecting it, and its
+            // key must not be kept
+            self.update_accounts = false;
+            self.session = None;
+            self.sessions.remove(&self.collections[self.selected_coll_index.unwrap()].file_path);
             self.collections.remove(self.selected_coll_index.unwrap());
             self.unselect_collection()?;
             self.update_collections = true;
@@ -286,33 +610,50 @@ impl Manager {
 
     // accounts actions
     fn load_accounts(&mut self) -> Result<(), Box<dyn Error>> {
+        let collection: &Collection = &self.collections[self.selected_coll_index.unwrap()];
+        if let Some(session) = self.sessions.remove(&collection.file_path) {
+            // if the file changed since it was unlocked, the password is asked as usual
+            if let Ok(accounts) = reopen_accounts(&collection.file_path, &session) {
+                self.accounts = Some(accounts);
+                self.session = Some(session);
+                return Ok(());
+            }
+        }
         match
             get_accounts(
-                &self.collections[self.selected_coll_index.unwrap()].file_path,
-                &mut self.password,
-                &self.salt
+                &collection.file_path,
+                collection.salt.as_ref(),
+                collection.cipher,
+                &collection.kdf,
+                &self.config
             )
         {
-            Ok(a) => {
+            Ok((a, session)) => {
                 self.accounts = Some(a);
+                self.session = Some(session);
             }
-            Err(e) => {
-                match e.downcast_ref::<InquireError>() {
-                    Some(er) => {
-                        match *er {
-                            InquireError::OperationCanceled => {
-                                clear_terminal_screen();
-                                println!("Incorrect password! Try again");
-                            }
-                            _ => {
-                                return Err(e);
-                            }
-                        }
-                    }
-                    None => {
-                        return Err(e);
-                    }
+            Err(VaultError::Prompt(InquireError::OperationCanceled)) => {
+                clear_terminal_screen();
+                println!("Incorrect password! Try again");
+            }
+            Err(
+                e @ (
+                    VaultError::CorruptFile(_)
+                    | VaultError::UnsupportedVersion(_)
+                    | VaultError::MissingSalt
+                    | VaultError::Serialization(_)
+                    | VaultError::Io(_)
+                ),
+            ) => {
+                clear_terminal_screen();
+                println!("This collection's accounts file can't be opened: {}", e);
+                if !list_backups(&collection.file_path).is_empty() {
+                    println!("One of its backups can be restored instead");
                 }
+                wait_for_enter();
+            }
+            Err(e) => {
+                return Err(Box::new(e));
             }
         }
         Ok(())
@@ -336,14 +677,192 @@ impl Manager {
         Ok(())
     }
 
+    /// Asks for a query and lets the user choose one of the matching accounts, best first
+    fn search_accounts(&mut self) -> Result<(), Box<dyn Error>> {
+        let query: String = match Text::new("Search:").prompt() {
+            Ok(query) => query,
+            Err(InquireError::OperationCanceled) => {
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(Box::new(e));
+            }
+        };
+
+        let accounts: &Vec<Account> = self.accounts.as_ref().unwrap();
+        let hits: Vec<SearchHit> = search(accounts, &query);
+        if hits.is_empty() {
+            println!("No accounts match \"{}\"", query);
+            wait_for_enter();
+            return Ok(());
+        }
+        let results: Vec<String> = hits
+            .iter()
+            .map(|hit| format!("{} (matched {})", accounts[hit.index].name, hit.field))
+            .collect();
+
+        match Select::new("Choose an account:", results).raw_prompt() {
+            Ok(choice) => self.select_account(hits[choice.index].index),
+            Err(InquireError::OperationCanceled) => Ok(()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Asks for one of the tags used in the collection, and only lists the accounts that have it
+    fn filter_by_tag(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut tags: Vec<String> = vec![];
+        for account in self.accounts.as_ref().unwrap() {
+            for tag in account.tags() {
+                if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    tags.push(tag.clone());
+                }
+            }
+        }
+        tags.sort_by_key(|tag| tag.to_lowercase());
+
+        match Select::new("Show the accounts tagged:", tags).prompt() {
+            Ok(tag) => {
+                self.tag_filter = Some(tag);
+                Ok(())
+            }
+            Err(InquireError::OperationCanceled) => Ok(()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Decrypts the accounts of a collection for a search, with its cached session if there is
+    /// one. Otherwise its master password is asked, and the collection is skipped if the user
+    /// presses ESC or the password is wrong. Collections without an accounts file are skipped too
+    fn unlock_for_search(&mut self, index: usize) -> Result<Option<Vec<Account>>, Box<dyn Error>> {
+        let collection: &Collection = &self.collections[index];
+        let data: Vec<u8> = match fs::read(&collection.file_path) {
+            Ok(data) => data,
+            Err(_) => {
+                return Ok(None);
+            }
+        };
+        if let Some(session) = self.sessions.get(&collection.file_path) {
+            if let Ok(accounts) = reopen_accounts(&collection.file_path, session) {
+                return Ok(Some(accounts));
+            }
+        }
+
+        let mut password: String = match
+            Password::new(&format!("Master password of \"{}\":", collection.name))
+                .without_confirmation()
+                .with_display_mode(PasswordDisplayMode::Masked)
+                .with_help_message("ESC to skip this collection")
+                .prompt()
+        {
+            Ok(p) => p,
+            Err(InquireError::OperationCanceled) => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(Box::new(e));
+            }
+        };
+        let unlocked = open_accounts(
+            &data,
+            password.as_bytes(),
+            collection.salt.as_ref(),
+            collection.cipher
+        );
+        password.zeroize();
+        match unlocked {
+            Ok((accounts, session)) => {
+                self.sessions.insert(collection.file_path.clone(), session);
+                Ok(Some(accounts))
+            }
+            Err(e) => {
+                println!("Skipping \"{}\": {}", collection.name, e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Searches the accounts of every collection, and opens the one the user chooses
+    fn search_all_collections(&mut self) -> Result<(), Box<dyn Error>> {
+        let query: String = match Text::new("Search all collections:").prompt() {
+            Ok(query) => query,
+            Err(InquireError::OperationCanceled) => {
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(Box::new(e));
+            }
+        };
+
+        // the accounts of every collection that was unlocked, and the matches in each of them
+        let mut unlocked: Vec<(usize, Vec<Account>)> = vec![];
+        let mut hits: Vec<(usize, SearchHit)> = vec![];
+        for index in 0..self.collections.len() {
+            if let Some(accounts) = self.unlock_for_search(index)? {
+                hits.extend(
+                    search(&accounts, &query)
+                        .into_iter()
+                        .map(|hit| (unlocked.len(), hit))
+                );
+                unlocked.push((index, accounts));
+            }
+        }
+        hits.sort_by_key(|(_, hit)| std::cmp::Reverse(hit.score));
+
+        let chosen: Option<usize> = if hits.is_empty() {
+            println!("No accounts match \"{}\"", query);
+            wait_for_enter();
+            None
+        } else {
+            let results: Vec<String> = hits
+                .iter()
+                .map(|(u, hit)| {
+                    let (index, accounts) = &unlocked[*u];
+                    format!(
+                        "{} [{}] (matched {})",
+                        accounts[hit.index].name,
+                        self.collections[*index].name,
+                        hit.field
+                    )
+                })
+                .collect();
+            match Select::new("Choose an account:", results).raw_prompt() {
+                Ok(choice) => Some(choice.index),
+                Err(InquireError::OperationCanceled) => None,
+                Err(e) => {
+                    unlocked.zeroize();
+                    return Err(Box::new(e));
+                }
+            }
+        };
+
+        if let Some(chosen) = chosen {
+            let (u, hit) = &hits[chosen];
+            let (index, accounts) = unlocked.swap_remove(*u);
+            let file_path: &String = &self.collections[index].file_path;
+            self.selected_coll_index = Some(index);
+            self.session = self.sessions.remove(file_path);
+            self.accounts = Some(accounts);
+            self.selected_acc_index = Some(hit.index);
+        }
+        unlocked.zeroize();
+        Ok(())
+    }
+
     fn unselect_account(&mut self) -> Result<(), Box<dyn Error>> {
-        if self.update_accounts && self.accounts.is_some() {
+        if
+            let (true, Some(accounts), Some(session)) = (
+                self.update_accounts,
+                &self.accounts,
+                &self.session,
+            )
+        {
             save_accounts(
-                self.accounts.as_ref().unwrap(),
+                accounts,
                 &self.collections.get(self.selected_coll_index.unwrap()).unwrap().file_path,
-                &self.password,
-                &self.salt
+                session,
+                self.config.backup_count
             )?;
+            self.update_accounts = false;
         }
         self.selected_acc_index = None;
         self.show_password = false;
@@ -378,9 +897,7 @@ impl Manager {
                 Ok(())
             }
             Err(InquireError::OperationCanceled) => Ok(()),
-            Err(e) => {
-                return Err(Box::new(e));
-            }
+            Err(e) => Err(Box::new(e)),
         }
     }
 
@@ -406,12 +923,89 @@ impl Manager {
         Ok(())
     }
 
-    fn copy_to_clipboard(&self) {
+    fn copy_field(&mut self) -> Result<(), Box<dyn Error>> {
+        let account: &Account = self.selected_account();
+        let names: Vec<String> = account
+            .fields()
+            .iter()
+            .map(|f| f.name.clone())
+            .collect();
+        match Select::new("Choose a field to copy:", names).raw_prompt() {
+            Ok(choice) => {
+                let field: &CustomField = &account.fields()[choice.index];
+                let what: String = format!("\"{}\" field", field.name);
+                self.copy(field.value().to_owned(), &what);
+                Ok(())
+            }
+            Err(InquireError::OperationCanceled) => Ok(()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    fn next_hotp_code(&mut self) -> Result<(), Box<dyn Error>> {
+        let code: Option<String> = next_hotp_code(
+            self.accounts.as_mut().unwrap(),
+            self.selected_acc_index.unwrap(),
+            &self.collections[self.selected_coll_index.unwrap()].file_path,
+            self.session.as_ref().unwrap()
+        )?;
+        if let Some(code) = code {
+            self.update_accounts = false;
+            println!("HOTP code: {}", code);
+            wait_for_enter();
+        }
+        Ok(())
+    }
+
+    fn selected_account(&self) -> &Account {
         self.accounts
             .as_ref()
             .unwrap()
             .get(self.selected_acc_index.unwrap())
             .unwrap()
-            .copy_to_clipboard()
+    }
+
+    fn clipboard(&mut self) -> Result<&mut dyn Clipboard, ClipboardError> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(Box::new(SystemClipboard::new()?));
+        }
+        Ok(self.clipboard.as_mut().unwrap().as_mut())
+    }
+
+    /// Copies value and waits until the clipboard is cleared, as set in the config. If there is no
+    /// clipboard, the error is shown and the menu keeps working
+    fn copy(&mut self, mut value: String, what: &str) {
+        let seconds: u64 = self.config.clipboard_clear_seconds;
+        let copied: Result<(), ClipboardError> = self
+            .clipboard()
+            .and_then(|clipboard| copy_with_countdown(clipboard, &value, what, seconds));
+        value.zeroize();
+        if let Err(e) = copied {
+            println!("Could not copy the {}: {}", what, e);
+            wait_for_enter();
+        }
+    }
+
+    /// Copies the username (or the email, as login says) and then the password, each on a key
+    /// press, so both can be pasted into a login form in a row
+    fn type_ahead(&mut self, login: &str) {
+        let account: &Account = self.selected_account();
+        let mut login_value: String = match login {
+            "email" => account.email().to_owned(),
+            _ => account.username().to_owned(),
+        };
+        let mut password: String = account.password().to_owned();
+        let seconds: u64 = self.config.clipboard_clear_seconds;
+        let copied: Result<(), ClipboardError> = self
+            .clipboard()
+            .and_then(|clipboard| {
+                copy_sequence(clipboard, &[(login, &login_value), ("password", &password)], seconds)
+            });
+        login_value.zeroize();
+        password.zeroize();
+        if let Err(e) = copied {
+            println!("Could not copy the {} and password: {}", login, e);
+            wait_for_enter();
+        }
     }
 }
@@ -0,0 +1,473 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{ Hmac, Mac };
+use inquire::validator::Validation;
+use inquire::{ CustomType, InquireError, Select, Text };
+use serde::{ Deserialize, Serialize };
+use sha1::Sha1;
+use sha2::{ Sha256, Sha512 };
+use std::fmt::{ self, Display };
+use zeroize::Zeroize;
+
+use crate::error::OtpError;
+use crate::utils::unix_time;
+
+const URI_SCHEME: &str = "otpauth://";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OtpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpAlgorithm {
+    fn parse(name: &str) -> Result<OtpAlgorithm, OtpError> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA1" => Ok(OtpAlgorithm::Sha1),
+            "SHA256" => Ok(OtpAlgorithm::Sha256),
+            "SHA512" => Ok(OtpAlgorithm::Sha512),
+            _ => Err(OtpError::UnsupportedAlgorithm(name.to_owned())),
+        }
+    }
+}
+
+fn default_digits() -> u32 {
+    6
+}
+
+fn default_period() -> u64 {
+    30
+}
+
+/// Time-based one-time passwords (RFC 6238), as used by most two-factor authenticator apps
+#[derive(Serialize, Deserialize, Debug, Clone, Zeroize)]
+pub struct Totp {
+    /// Base32, uppercase and without padding
+    secret: String,
+    #[serde(default)]
+    #[zeroize(skip)]
+    algorithm: OtpAlgorithm,
+    #[serde(default = "default_digits")]
+    digits: u32,
+    /// How many seconds each code is valid for
+    #[serde(default = "default_period")]
+    period: u64,
+}
+
+impl Totp {
+    /// Reads a base32 secret, or an otpauth://totp/ URI like the ones in the QR codes sites show
+    pub fn parse(input: &str) -> Result<Totp, OtpError> {
+        let uri: OtpUri = OtpUri::parse(input, "totp")?;
+        if uri.counter.is_some() {
+            return Err(OtpError::InvalidUri("TOTP URIs don't have a counter"));
+        }
+        Ok(Totp {
+            secret: uri.secret,
+            algorithm: uri.algorithm,
+            digits: uri.digits,
+            period: uri.period.unwrap_or(default_period()),
+        })
+    }
+
+    /// The code for the given Unix time
+    pub fn code_at(&self, time: u64) -> String {
+        hotp(&self.secret, time / self.period, self.algorithm, self.digits)
+    }
+
+    /// The current code, and for how many more seconds it's valid
+    pub fn now(&self) -> (String, u64) {
+        let time: u64 = unix_time();
+        (self.code_at(time), self.period - (time % self.period))
+    }
+}
+
+impl Display for Totp {
+    /// Shows the current code and a countdown until the next one
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (code, remaining) = self.now();
+        let width: usize = 10;
+        let filled: usize = (((remaining as usize) * width) / (self.period as usize)).max(1);
+        write!(
+            f,
+            "{} [{}{}] {}s",
+            code,
+            "#".repeat(filled),
+            " ".repeat(width.saturating_sub(filled)),
+            remaining
+        )
+    }
+}
+
+/// Counter-based one-time passwords (RFC 4226), used by some hardware tokens. Both sides keep
+/// a counter that goes up with every code, so the counter must be saved after each one
+#[derive(Serialize, Deserialize, Debug, Clone, Zeroize)]
+pub struct Hotp {
+    /// Base32, uppercase and without padding
+    secret: String,
+    #[serde(default)]
+    #[zeroize(skip)]
+    algorithm: OtpAlgorithm,
+    #[serde(default = "default_digits")]
+    digits: u32,
+    /// The counter of the next code
+    counter: u64,
+}
+
+impl Hotp {
+    /// Reads a base32 secret, or an otpauth://hotp/ URI. A bare secret starts at counter 0
+    pub fn parse(input: &str) -> Result<Hotp, OtpError> {
+        let uri: OtpUri = OtpUri::parse(input, "hotp")?;
+        if uri.period.is_some() {
+            return Err(OtpError::InvalidUri("HOTP URIs don't have a period"));
+        }
+        Ok(Hotp {
+            secret: uri.secret,
+            algorithm: uri.algorithm,
+            digits: uri.digits,
+            counter: uri.counter.unwrap_or(0),
+        })
+    }
+
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
+
+    pub fn set_counter(&mut self, counter: u64) {
+        self.counter = counter;
+    }
+
+    /// True if other generates the codes of the same token, whatever its counter
+    pub fn same_token(&self, other: &Hotp) -> bool {
+        self.secret == other.secret && self.algorithm == other.algorithm && self.digits == other.digits
+    }
+
+    /// The code for the current counter. The counter is incremented, so the account must be saved
+    /// before the code is used
+    pub fn next_code(&mut self) -> String {
+        let code: String = hotp(&self.secret, self.counter, self.algorithm, self.digits);
+        self.counter += 1;
+        code
+    }
+}
+
+/// Uppercases the secret and removes spaces, dashes and padding, which authenticator apps
+/// usually accept, and checks that it's valid base32
+fn normalize_secret(secret: &str) -> Result<String, OtpError> {
+    let secret: String = secret
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '='))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match BASE32_NOPAD.decode(secret.as_bytes()) {
+        Ok(mut key) if !key.is_empty() => {
+            key.zeroize();
+            Ok(secret)
+        }
+        _ => Err(OtpError::InvalidSecret),
+    }
+}
+
+fn parse_digits(value: &str) -> Result<u32, OtpError> {
+    match value.parse() {
+        Ok(digits) if (6..=8).contains(&digits) => Ok(digits),
+        _ => Err(OtpError::InvalidDigits),
+    }
+}
+
+/// A base32 secret, or an otpauth://KIND/LABEL?secret=...&... URI. The label and the issuer are
+/// only informative, so they are ignored
+struct OtpUri {
+    secret: String,
+    algorithm: OtpAlgorithm,
+    digits: u32,
+    period: Option<u64>,
+    counter: Option<u64>,
+}
+
+impl OtpUri {
+    fn parse(input: &str, expected_kind: &'static str) -> Result<OtpUri, OtpError> {
+        let input: &str = input.trim();
+        let mut uri = OtpUri {
+            secret: String::new(),
+            algorithm: OtpAlgorithm::default(),
+            digits: default_digits(),
+            period: None,
+            counter: None,
+        };
+        let Some(rest) = input.strip_prefix(URI_SCHEME) else {
+            uri.secret = normalize_secret(input)?;
+            return Ok(uri);
+        };
+
+        let (kind, rest) = rest
+            .split_once('/')
+            .ok_or(OtpError::InvalidUri("the label is missing"))?;
+        if !kind.eq_ignore_ascii_case(expected_kind) {
+            return Err(OtpError::WrongKind(expected_kind));
+        }
+        let (_, query) = rest.split_once('?').ok_or(OtpError::InvalidUri("the secret is missing"))?;
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            match key {
+                "secret" => {
+                    uri.secret = normalize_secret(value)?;
+                }
+                "algorithm" => {
+                    uri.algorithm = OtpAlgorithm::parse(value)?;
+                }
+                "digits" => {
+                    uri.digits = parse_digits(value)?;
+                }
+                "period" => {
+                    uri.period = match value.parse() {
+                        Ok(period) if period > 0 => Some(period),
+                        _ => {
+                            return Err(OtpError::InvalidUri("the period must be a positive number"));
+                        }
+                    };
+                }
+                "counter" => {
+                    uri.counter = match value.parse() {
+                        Ok(counter) => Some(counter),
+                        Err(_) => {
+                            return Err(OtpError::InvalidUri("the counter must be a number"));
+                        }
+                    };
+                }
+                _ => {}
+            }
+        }
+        if uri.secret.is_empty() {
+            return Err(OtpError::InvalidUri("the secret is missing"));
+        }
+        Ok(uri)
+    }
+}
+
+fn hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac: M = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// RFC 4226: the HMAC of the counter, dynamically truncated to the given number of digits.
+/// TOTP is the same with the number of periods since the Unix epoch as the counter
+fn hotp(secret: &str, counter: u64, algorithm: OtpAlgorithm, digits: u32) -> String {
+    // the secret was checked when it was parsed
+    let mut key: Vec<u8> = BASE32_NOPAD.decode(secret.as_bytes()).unwrap_or_default();
+    let counter: [u8; 8] = counter.to_be_bytes();
+    let mut mac: Vec<u8> = match algorithm {
+        OtpAlgorithm::Sha1 => hmac::<Hmac<Sha1>>(&key, &counter),
+        OtpAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(&key, &counter),
+        OtpAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(&key, &counter),
+    };
+    key.zeroize();
+
+    let offset: usize = (mac[mac.len() - 1] & 0x0f) as usize;
+    let binary: u32 = u32::from_be_bytes(mac[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    mac.zeroize();
+    let code: u64 = (binary as u64) % 10u64.pow(digits);
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// How an account's two-factor codes are generated, if it uses them
+#[derive(Clone)]
+pub enum TwoFactor {
+    None,
+    Totp(Totp),
+    Hotp(Hotp),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TwoFactorChoice {
+    Keep,
+    None,
+    Totp,
+    Hotp,
+}
+
+impl Display for TwoFactorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TwoFactorChoice::Keep => write!(f, "Keep the current one"),
+            TwoFactorChoice::None => write!(f, "None"),
+            TwoFactorChoice::Totp => write!(f, "Time-based codes (TOTP)"),
+            TwoFactorChoice::Hotp => write!(f, "Counter-based codes (HOTP)"),
+        }
+    }
+}
+
+/// Reads a secret or URI with parse, asking again until it's valid
+fn prompt_secret<T: 'static>(message: &str, parse: fn(&str) -> Result<T, OtpError>) -> Result<T, InquireError> {
+    let input: String = Text::new(message)
+        .with_validator(move |input: &str| {
+            Ok(match parse(input) {
+                Ok(_) => Validation::Valid,
+                Err(e) => Validation::Invalid(e.into()),
+            })
+        })
+        .prompt()?;
+    // it was validated above
+    parse(&input).map_err(|e| InquireError::Custom(Box::new(e)))
+}
+
+/// Asks how the account's two-factor codes are generated. When editing an account, current is
+/// what it has now, which can also be kept
+pub fn prompt_two_factor(current: Option<TwoFactor>) -> Result<TwoFactor, InquireError> {
+    let mut choices: Vec<TwoFactorChoice> = vec![
+        TwoFactorChoice::None,
+        TwoFactorChoice::Totp,
+        TwoFactorChoice::Hotp
+    ];
+    let current: TwoFactor = current.unwrap_or(TwoFactor::None);
+    if !matches!(current, TwoFactor::None) {
+        choices.insert(0, TwoFactorChoice::Keep);
+    }
+
+    match Select::new("two-factor codes: ", choices).prompt()? {
+        TwoFactorChoice::Keep => Ok(current),
+        TwoFactorChoice::None => Ok(TwoFactor::None),
+        TwoFactorChoice::Totp => {
+            let totp: Totp = prompt_secret("TOTP secret or otpauth://totp/ URI: ", Totp::parse)?;
+            Ok(TwoFactor::Totp(totp))
+        }
+        TwoFactorChoice::Hotp => {
+            let mut hotp: Hotp = prompt_secret("HOTP secret or otpauth://hotp/ URI: ", Hotp::parse)?;
+            let counter: u64 = CustomType::new("HOTP counter: ")
+                .with_default(hotp.counter())
+                .with_help_message("the counter of the next code, 0 for a new token")
+                .prompt()?;
+            hotp.set_counter(counter);
+            Ok(TwoFactor::Hotp(hotp))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The secrets of RFC 4226 and RFC 6238: "1234567890" repeated to the length of the hash
+    fn rfc_secret(len: usize) -> String {
+        let ascii: Vec<u8> = b"1234567890".iter().copied().cycle().take(len).collect();
+        BASE32_NOPAD.encode(&ascii)
+    }
+
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let codes: [&str; 10] = [
+            "755224", "287082", "359152", "969429", "338314",
+            "254676", "287922", "162583", "399871", "520489",
+        ];
+        let mut hotp: Hotp = Hotp::parse(&rfc_secret(20)).unwrap();
+        for (counter, code) in codes.iter().enumerate() {
+            assert_eq!(hotp.counter(), counter as u64);
+            assert_eq!(hotp.next_code(), *code);
+        }
+        assert_eq!(hotp.counter(), 10);
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238() {
+        // time, then the SHA1, SHA256 and SHA512 codes
+        let vectors: [(u64, [&str; 3]); 6] = [
+            (59, ["94287082", "46119246", "90693936"]),
+            (1111111109, ["07081804", "68084774", "25091201"]),
+            (1111111111, ["14050471", "67062674", "99943326"]),
+            (1234567890, ["89005924", "91819424", "93441116"]),
+            (2000000000, ["69279037", "90698825", "38618901"]),
+            (20000000000, ["65353130", "77737706", "47863826"]),
+        ];
+        let algorithms: [(&str, usize); 3] = [("SHA1", 20), ("SHA256", 32), ("SHA512", 64)];
+        for (i, (algorithm, secret_len)) in algorithms.iter().enumerate() {
+            let uri: String = format!(
+                "otpauth://totp/Example:alice?secret={}&algorithm={}&digits=8&period=30",
+                rfc_secret(*secret_len),
+                algorithm
+            );
+            let totp: Totp = Totp::parse(&uri).unwrap();
+            for (time, codes) in &vectors {
+                assert_eq!(totp.code_at(*time), codes[i], "{} at {}", algorithm, time);
+            }
+        }
+    }
+
+    #[test]
+    fn secrets_are_normalized() {
+        let totp: Totp = Totp::parse(" gezd gnbv-gy3t qojq gezd gnbv gy3t qojq== ").unwrap();
+        assert_eq!(totp.secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(totp.digits, 6);
+        assert_eq!(totp.period, 30);
+        assert_eq!(totp.algorithm, OtpAlgorithm::Sha1);
+    }
+
+    #[test]
+    fn hotp_uris_keep_their_counter() {
+        let hotp: Hotp = Hotp::parse(
+            "otpauth://hotp/Example:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=5&issuer=Example"
+        ).unwrap();
+        assert_eq!(hotp.counter(), 5);
+        assert_eq!(hotp.clone().next_code(), "254676");
+    }
+
+    #[test]
+    fn invalid_secrets_and_uris_are_rejected() {
+        let secret: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert!(matches!(Totp::parse("not base32!"), Err(OtpError::InvalidSecret)));
+        assert!(matches!(Totp::parse(""), Err(OtpError::InvalidSecret)));
+        assert!(
+            matches!(
+                Totp::parse(&format!("otpauth://hotp/a?secret={}", secret)),
+                Err(OtpError::WrongKind("totp"))
+            )
+        );
+        assert!(
+            matches!(
+                Hotp::parse(&format!("otpauth://totp/a?secret={}", secret)),
+                Err(OtpError::WrongKind("hotp"))
+            )
+        );
+        assert!(matches!(Totp::parse("otpauth://totp/a?issuer=x"), Err(OtpError::InvalidUri(_))));
+        assert!(matches!(Totp::parse("otpauth://totp"), Err(OtpError::InvalidUri(_))));
+        assert!(
+            matches!(
+                Totp::parse(&format!("otpauth://totp/a?secret={}&algorithm=MD5", secret)),
+                Err(OtpError::UnsupportedAlgorithm(_))
+            )
+        );
+        for digits in ["5", "9", "six"] {
+            assert!(
+                matches!(
+                    Totp::parse(&format!("otpauth://totp/a?secret={}&digits={}", secret, digits)),
+                    Err(OtpError::InvalidDigits)
+                )
+            );
+        }
+        assert!(
+            matches!(
+                Totp::parse(&format!("otpauth://totp/a?secret={}&period=0", secret)),
+                Err(OtpError::InvalidUri(_))
+            )
+        );
+        assert!(
+            matches!(
+                Totp::parse(&format!("otpauth://totp/a?secret={}&counter=1", secret)),
+                Err(OtpError::InvalidUri(_))
+            )
+        );
+        assert!(
+            matches!(
+                Hotp::parse(&format!("otpauth://hotp/a?secret={}&period=30", secret)),
+                Err(OtpError::InvalidUri(_))
+            )
+        );
+        assert!(
+            matches!(
+                Hotp::parse(&format!("otpauth://hotp/a?secret={}&counter=-1", secret)),
+                Err(OtpError::InvalidUri(_))
+            )
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use inquire::{ Confirm, CustomType, InquireError, Text };
+use zeroize::Zeroize;
+
+use crate::error::GeneratorError;
+use crate::random;
+
+/// 7776 (6^5) distinct words, one per line, so each word is worth one roll of five dice.
+/// It's embedded in the executable, so generating a passphrase never depends on external files
+const WORDLIST: &str = include_str!("../data/wordlist.txt");
+
+pub fn words() -> Vec<&'static str> {
+    WORDLIST.lines().collect()
+}
+
+/// How a passphrase is built: words random words joined by separator. capitalize makes the
+/// first letter of every word uppercase, and add_digit appends a random digit to a random word,
+/// which helps with sites that require digits and adds a few bits of entropy
+#[derive(Clone, PartialEq, Eq)]
+pub struct PassphraseOptions {
+    pub words: usize,
+    pub separator: String,
+    pub capitalize: bool,
+    pub add_digit: bool,
+}
+
+impl Default for PassphraseOptions {
+    fn default() -> Self {
+        PassphraseOptions {
+            words: 6,
+            separator: String::from("-"),
+            capitalize: false,
+            add_digit: false,
+        }
+    }
+}
+
+impl PassphraseOptions {
+    pub fn check(&self) -> Result<(), GeneratorError> {
+        if self.words == 0 {
+            return Err(GeneratorError::NoWords);
+        }
+        Ok(())
+    }
+
+    /// How many bits of entropy a passphrase generated with these options has, assuming the
+    /// attacker knows the wordlist and the options. Capitalization doesn't add any, since it's
+    /// applied to every word
+    pub fn entropy_bits(&self) -> f64 {
+        let mut bits: f64 = (self.words as f64) * (words().len() as f64).log2();
+        if self.add_digit {
+            bits += (10.0 * (self.words as f64)).log2();
+        }
+        bits
+    }
+}
+
+/// Generates a passphrase with the OS CSPRNG
+pub fn generate_passphrase(options: &PassphraseOptions) -> Result<String, GeneratorError> {
+    options.check()?;
+
+    let wordlist: Vec<&str> = words();
+    let mut chosen: Vec<String> = (0..options.words)
+        .map(|_| {
+            let word: &str = wordlist[random::below(wordlist.len())];
+            if options.capitalize {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            } else {
+                word.to_owned()
+            }
+        })
+        .collect();
+
+    if options.add_digit {
+        let index: usize = random::below(chosen.len());
+        let digit: char = char::from(b'0' + (random::below(10) as u8));
+        chosen[index].push(digit);
+    }
+
+    let passphrase: String = chosen.join(&options.separator);
+    chosen.zeroize();
+    Ok(passphrase)
+}
+
+/// Asks for the passphrase options, and generates passphrases until the user accepts one
+pub fn prompt_passphrase() -> Result<String, InquireError> {
+    let default: PassphraseOptions = PassphraseOptions::default();
+    let options: PassphraseOptions = loop {
+        let words: usize = CustomType::new("Number of words: ")
+            .with_default(default.words)
+            .prompt()?;
+        let separator: String = Text::new("Separator: ")
+            .with_default(&default.separator)
+            .prompt()?;
+        let capitalize: bool = Confirm::new("Capitalize the words?")
+            .with_default(default.capitalize)
+            .prompt()?;
+        let add_digit: bool = Confirm::new("Add a digit?")
+            .with_default(default.add_digit)
+            .prompt()?;
+
+        let options = PassphraseOptions { words, separator, capitalize, add_digit };
+        match options.check() {
+            Ok(_) => {
+                break options;
+            }
+            Err(e) => println!("Invalid options: {}. Try again", e),
+        }
+    };
+
+    loop {
+        let mut passphrase: String = match generate_passphrase(&options) {
+            Ok(passphrase) => passphrase,
+            Err(e) => {
+                return Err(InquireError::Custom(Box::new(e)));
+            }
+        };
+        println!(
+            "Generated passphrase: {} ({:.0} bits of entropy)",
+            passphrase,
+            options.entropy_bits()
+        );
+        if Confirm::new("Use this passphrase?").with_default(true).prompt()? {
+            return Ok(passphrase);
+        }
+        passphrase.zeroize();
+    }
+}
@@ -0,0 +1,37 @@
+use rand::{ rngs::OsRng, seq::SliceRandom, Rng, RngCore };
+
+// Every random value that protects a vault (salts, nonces, keys, generated passwords) comes from
+// this module, so they are all generated by the operating system's CSPRNG
+
+/// Fills bytes with random data from the OS CSPRNG
+pub fn fill(bytes: &mut [u8]) {
+    OsRng.fill_bytes(bytes);
+}
+
+pub fn salt() -> [u8; 32] {
+    let mut salt: [u8; 32] = [0u8; 32];
+    fill(&mut salt);
+    salt
+}
+
+/// Creates a random nonce (also called initial vector, or iv) of "size" bytes.
+/// A key is used for every save of an accounts file, across runs, so nothing short of a counter
+/// saved with it could rule out a repeated nonce. The protection is the size of the nonce instead:
+/// with the 192 bit nonces of XChaCha20-Poly1305, the default, a collision can't realistically
+/// happen no matter how many times a file is saved. The 96 bit nonces of AES-GCM are only safe
+/// for about 2^32 saves with the same key, which is far more than a password manager does
+pub fn nonce(size: usize) -> Vec<u8> {
+    let mut nonce: Vec<u8> = vec![0u8; size];
+    fill(&mut nonce);
+    nonce
+}
+
+/// Picks a uniformly distributed number in 0..n
+pub fn below(n: usize) -> usize {
+    OsRng.gen_range(0..n)
+}
+
+/// Shuffles items in place (Fisher-Yates)
+pub fn shuffle<T>(items: &mut [T]) {
+    items.shuffle(&mut OsRng);
+}
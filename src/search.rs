@@ -0,0 +1,111 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use std::fmt::{ self, Display };
+
+use crate::accounts::Account;
+use crate::fields::FieldKind;
+
+/// Where a search matched. Matches in the name count the most, and matches in the notes the least
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SearchField {
+    Name,
+    Username,
+    Email,
+    Url,
+    Tag,
+    Folder,
+    Notes,
+    Custom,
+}
+
+impl SearchField {
+    fn weight(&self) -> i64 {
+        match self {
+            SearchField::Name => 3,
+            SearchField::Username | SearchField::Email | SearchField::Url | SearchField::Tag => 2,
+            SearchField::Folder | SearchField::Notes | SearchField::Custom => 1,
+        }
+    }
+}
+
+impl Display for SearchField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchField::Name => write!(f, "name"),
+            SearchField::Username => write!(f, "username"),
+            SearchField::Email => write!(f, "email"),
+            SearchField::Url => write!(f, "url"),
+            SearchField::Tag => write!(f, "tag"),
+            SearchField::Folder => write!(f, "folder"),
+            SearchField::Notes => write!(f, "notes"),
+            SearchField::Custom => write!(f, "custom field"),
+        }
+    }
+}
+
+/// An account that matched a search
+#[derive(Debug)]
+pub struct SearchHit {
+    /// Position of the account in the slice that was searched
+    pub index: usize,
+    pub score: i64,
+    /// Where the best match was
+    pub field: SearchField,
+}
+
+/// The text of an account that is searched. Hidden custom fields and the password never are
+fn searchable(account: &Account) -> Vec<(SearchField, &str)> {
+    let mut texts: Vec<(SearchField, &str)> = vec![
+        (SearchField::Name, account.name.as_str()),
+        (SearchField::Username, account.username()),
+        (SearchField::Email, account.email()),
+        (SearchField::Notes, account.hints())
+    ];
+    texts.extend(account.urls().iter().map(|url| (SearchField::Url, url.as_str())));
+    texts.extend(account.tags().iter().map(|tag| (SearchField::Tag, tag.as_str())));
+    if let Some(folder) = account.folder() {
+        texts.push((SearchField::Folder, folder));
+    }
+    for field in account.fields() {
+        texts.push((SearchField::Custom, field.name.as_str()));
+        if field.kind != FieldKind::Hidden {
+            texts.push((SearchField::Custom, field.value()));
+        }
+    }
+    texts
+}
+
+/// Fuzzy matches every word of query against the accounts, like "gthb work" would find a GitHub
+/// account with a work email. An account is only returned if every word matches somewhere.
+/// The best matches come first, and an empty query returns every account in its order
+pub fn search(accounts: &[Account], query: &str) -> Vec<SearchHit> {
+    let matcher: SkimMatcherV2 = SkimMatcherV2::default().ignore_case();
+    let words: Vec<&str> = query.split_whitespace().collect();
+
+    let mut hits: Vec<SearchHit> = accounts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, account)| {
+            let texts: Vec<(SearchField, &str)> = searchable(account);
+            let mut score: i64 = 0;
+            let mut best: (i64, SearchField) = (0, SearchField::Name);
+            for word in &words {
+                let (word_score, field) = texts
+                    .iter()
+                    .filter_map(|(field, text)| {
+                        matcher.fuzzy_match(text, word).map(|s| (s * field.weight(), *field))
+                    })
+                    .max_by_key(|(s, _)| *s)?;
+                score += word_score;
+                if word_score > best.0 {
+                    best = (word_score, field);
+                }
+            }
+            Some(SearchHit { index, score, field: best.1 })
+        })
+        .collect();
+
+    // the sort is stable, so accounts with the same score keep their order
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+    hits
+}
@@ -0,0 +1,94 @@
+use crate::cryptography::{ self, CipherSuite, KdfParams, SecretKey };
+use crate::error::VaultError;
+use crate::random;
+
+/// An unlocked collection. It keeps the key derived from the master password (never the password
+/// itself), and the salt and Argon2 parameters it was derived with, so the accounts file can be
+/// saved again without running the key derivation
+pub struct Session {
+    key: SecretKey,
+    salt: [u8; 32],
+    kdf: KdfParams,
+    cipher: CipherSuite,
+}
+
+impl Session {
+    /// Derives the key of a new accounts file, with a new random salt
+    pub fn new(
+        password: &[u8],
+        cipher: CipherSuite,
+        kdf: &KdfParams
+    ) -> Result<Session, VaultError> {
+        Session::derive(password, &random::salt(), cipher, kdf)
+    }
+
+    fn derive(
+        password: &[u8],
+        salt: &[u8; 32],
+        cipher: CipherSuite,
+        kdf: &KdfParams
+    ) -> Result<Session, VaultError> {
+        Ok(Session {
+            key: cryptography::derive_key(password, salt, kdf)?,
+            salt: *salt,
+            kdf: *kdf,
+            cipher,
+        })
+    }
+
+    /// Decrypts an accounts file with its master password. Returns the session and the decrypted data.
+    /// Files with a header only need the password. legacy_salt is only used for files without
+    /// a header (v0), and cipher is used to save files that were encrypted with a cipher suite
+    /// that is only kept for compatibility
+    pub fn unlock(
+        data: &[u8],
+        password: &[u8],
+        legacy_salt: Option<&[u8; 32]>,
+        cipher: CipherSuite
+    ) -> Result<(Session, String), VaultError> {
+        let session: Session = match cryptography::read_header(data)? {
+            Some(header) => {
+                let cipher: CipherSuite = match header.cipher {
+                    CipherSuite::Aes128Gcm => cipher,
+                    c => c,
+                };
+                Session::derive(password, &header.salt, cipher, &header.kdf)?
+            }
+            None => {
+                let salt: &[u8; 32] = match legacy_salt {
+                    Some(s) => s,
+                    None => {
+                        return Err(VaultError::MissingSalt);
+                    }
+                };
+                Session::derive(password, salt, cipher, &KdfParams::default())?
+            }
+        };
+        let decrypted: String = cryptography::decrypt(data, &session.key)?;
+        Ok((session, decrypted))
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        cryptography::encrypt(data, &self.key, &self.salt, self.cipher, &self.kdf)
+    }
+
+    /// Decrypts another file encrypted with this session's key, like a backup of its accounts file.
+    /// Fails with WrongPassword if it was encrypted with a different salt or Argon2 parameters
+    pub fn decrypt(&self, data: &[u8]) -> Result<String, VaultError> {
+        match cryptography::read_header(data)? {
+            Some(header) if header.salt == self.salt && header.kdf == self.kdf => {
+                cryptography::decrypt(data, &self.key)
+            }
+            _ => Err(VaultError::WrongPassword),
+        }
+    }
+
+    /// Checks that password is the master password of this session
+    pub fn verify(&self, password: &[u8]) -> Result<(), VaultError> {
+        if cryptography::derive_key(password, &self.salt, &self.kdf)? == self.key {
+            Ok(())
+        } else {
+            Err(VaultError::WrongPassword)
+        }
+    }
+}
@@ -0,0 +1,757 @@
+use crossterm::{
+    cursor,
+    event::{ self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers },
+    queue,
+    style::Print,
+    terminal::{ self, ClearType },
+};
+use inquire::{ Confirm, InquireError, Password, Text };
+use std::collections::HashMap;
+use std::fmt::{ self, Display };
+use std::io::{ self, IsTerminal, Write };
+use std::sync::OnceLock;
+use zeroize::Zeroize;
+
+use crate::config::Config;
+use crate::passphrase;
+use crate::utils::{ unix_time, RawMode };
+
+// Estimates how many guesses an attacker needs to find a password, the way zxcvbn does: the
+// password is split into the pieces an attacker would try first (common passwords, dictionary
+// words, sequences, repeats, keyboard rows, dates), each piece is given a number of guesses, and
+// the cheapest way to cover the whole password wins. Whatever doesn't match any pattern is
+// counted as random characters
+
+/// Very common passwords, most common first
+const COMMON_PASSWORDS: &str = include_str!("../data/common_passwords.txt");
+/// Guesses per character that is not part of any pattern
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_GUESSES_MULTI_CHAR: f64 = 50.0;
+/// Keys a keyboard pattern can start on (both shifted and unshifted) of a US keyboard
+const KEYBOARD_STARTS: f64 = 94.0;
+const KEYBOARD_ROWS: [&str; 4] = ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./"];
+const MIN_YEAR_SPACE: f64 = 20.0;
+/// Passwords longer than this are only analyzed up to here, the rest counts as random characters
+const MAX_ANALYZED: usize = 100;
+const MAX_WORD_LEN: usize = 24;
+/// Guesses per second of an attacker with the encrypted file and a slow hash like Argon2
+const GUESSES_PER_SECOND: f64 = 1e4;
+const MINUTE: f64 = 60.0;
+const HOUR: f64 = MINUTE * 60.0;
+const DAY: f64 = HOUR * 24.0;
+const MONTH: f64 = DAY * 31.0;
+const YEAR: f64 = MONTH * 12.0;
+const CENTURY: f64 = YEAR * 100.0;
+/// The crack time (in seconds, at GUESSES_PER_SECOND) a password needs for scores 1 to 4. The
+/// score comes from the same crack time that is shown, so a very strong password can't be
+/// cracked in days
+const SCORE_CRACK_TIMES: [f64; 4] = [DAY, MONTH, YEAR, CENTURY];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pattern {
+    CommonPassword,
+    Word,
+    Sequence,
+    Repeat,
+    Keyboard,
+    Date,
+    Random,
+}
+
+struct Match {
+    start: usize,
+    end: usize,
+    /// log10 of the guesses needed for this piece
+    guesses: f64,
+    pattern: Pattern,
+}
+
+/// How hard a password is to guess
+pub struct Strength {
+    /// log10 of the estimated number of guesses
+    log_guesses: f64,
+    /// From 0 (very weak) to 4 (very strong)
+    pub score: u8,
+    warning: Option<&'static str>,
+}
+
+impl Strength {
+    /// Estimated time to crack the password offline, at GUESSES_PER_SECOND
+    pub fn crack_time(&self) -> String {
+        display_time(crack_seconds(self.log_guesses))
+    }
+
+    pub fn label(&self) -> &'static str {
+        score_label(self.score)
+    }
+}
+
+impl Display for Strength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, could be cracked in {}", self.label(), self.crack_time())?;
+        if let Some(warning) = self.warning {
+            write!(f, ". {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn score_label(score: u8) -> &'static str {
+    match score {
+        0 => "very weak",
+        1 => "weak",
+        2 => "fair",
+        3 => "strong",
+        _ => "very strong",
+    }
+}
+
+/// Ranks of the known passwords and words: a password with rank r is found within r guesses
+struct Dictionaries {
+    common: HashMap<&'static str, usize>,
+    words: HashMap<&'static str, usize>,
+}
+
+fn dictionaries() -> &'static Dictionaries {
+    static DICTIONARIES: OnceLock<Dictionaries> = OnceLock::new();
+    DICTIONARIES.get_or_init(|| {
+        let common: HashMap<&str, usize> = COMMON_PASSWORDS.lines()
+            .enumerate()
+            .map(|(rank, p)| (p, rank + 1))
+            .collect();
+        let words: Vec<&str> = passphrase::words();
+        let count: usize = words.len();
+        // the wordlist has no frequencies, so every word is as likely as a random pick from it
+        let words: HashMap<&str, usize> = words
+            .into_iter()
+            .map(|w| (w, count))
+            .collect();
+        Dictionaries { common, words }
+    })
+}
+
+pub fn estimate(password: &str) -> Strength {
+    let chars: Vec<char> = password.chars().collect();
+    let analyzed: &[char] = &chars[..chars.len().min(MAX_ANALYZED)];
+    let (mut log_guesses, matches) = most_guessable(analyzed);
+    log_guesses += ((chars.len() - analyzed.len()) as f64) * BRUTEFORCE_CARDINALITY.log10();
+
+    let seconds: f64 = crack_seconds(log_guesses);
+    let score: u8 = SCORE_CRACK_TIMES.iter().filter(|&&time| seconds >= time).count() as u8;
+
+    let warning: Option<&'static str> = if score >= 3 {
+        None
+    } else {
+        let longest = matches
+            .iter()
+            .filter(|m| m.pattern != Pattern::Random)
+            .max_by_key(|m| m.end - m.start);
+        match longest.map(|m| m.pattern) {
+            Some(Pattern::CommonPassword) => Some("This is a very common password"),
+            Some(Pattern::Word) if matches.len() == 1 => Some("A single word is easy to guess"),
+            Some(Pattern::Word) => Some("Dictionary words are easy to guess"),
+            Some(Pattern::Sequence) => Some("Sequences like abc or 6543 are easy to guess"),
+            Some(Pattern::Repeat) => Some("Repeats like aaa or abcabc are easy to guess"),
+            Some(Pattern::Keyboard) => Some("Rows of keys like qwerty are easy to guess"),
+            Some(Pattern::Date) => Some("Dates and years are easy to guess"),
+            _ if chars.len() < 10 => Some("Use a longer password"),
+            _ => None,
+        }
+    };
+
+    Strength { log_guesses, score, warning }
+}
+
+/// Finds the sequence of non overlapping matches covering the password that needs the fewest
+/// guesses. Like zxcvbn, a sequence of n pieces is multiplied by n!, since the attacker has to
+/// try the pieces in every order. Returns log10 of the guesses and the matches that were used
+fn most_guessable(chars: &[char]) -> (f64, Vec<Match>) {
+    let n: usize = chars.len();
+    if n == 0 {
+        return (0.0, vec![]);
+    }
+
+    let mut candidates: Vec<Match> = find_matches(chars);
+    for start in 0..n {
+        for end in start + 1..=n {
+            candidates.push(Match {
+                start,
+                end,
+                guesses: bruteforce_guesses(end - start),
+                pattern: Pattern::Random,
+            });
+        }
+    }
+
+    // best[k][l]: fewest (log10) guesses to cover chars[..k] with l matches, and the match used last
+    let mut best: Vec<Vec<Option<(f64, usize)>>> = vec![vec![None; n + 1]; n + 1];
+    best[0][0] = Some((0.0, usize::MAX));
+    let mut ending_at: Vec<Vec<usize>> = vec![vec![]; n + 1];
+    for (i, m) in candidates.iter().enumerate() {
+        ending_at[m.end].push(i);
+    }
+    for k in 1..=n {
+        for &i in &ending_at[k] {
+            let m: &Match = &candidates[i];
+            for l in 0..k {
+                if let Some((previous, _)) = best[m.start][l] {
+                    let total: f64 = previous + m.guesses;
+                    if best[k][l + 1].is_none_or(|(current, _)| total < current) {
+                        best[k][l + 1] = Some((total, i));
+                    }
+                }
+            }
+        }
+    }
+
+    let (mut count, mut log_guesses) = (0, f64::INFINITY);
+    for (l, entry) in best[n].iter().enumerate() {
+        if let Some((guesses, _)) = entry {
+            let total: f64 = guesses + log10_factorial(l);
+            if total < log_guesses {
+                (count, log_guesses) = (l, total);
+            }
+        }
+    }
+
+    let mut used: Vec<usize> = vec![];
+    let mut k: usize = n;
+    while count > 0 {
+        let (_, i) = best[k][count].unwrap_or((0.0, 0));
+        used.push(i);
+        k = candidates[i].start;
+        count -= 1;
+    }
+    let mut matches: Vec<Match> = vec![];
+    for (i, m) in candidates.into_iter().enumerate() {
+        if used.contains(&i) {
+            matches.push(m);
+        }
+    }
+    (log_guesses, matches)
+}
+
+fn find_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches: Vec<Match> = vec![];
+    dictionary_matches(chars, &mut matches);
+    sequence_matches(chars, &mut matches);
+    repeat_matches(chars, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    date_matches(chars, &mut matches);
+    for m in matches.iter_mut() {
+        let min: f64 = if m.end - m.start == 1 {
+            MIN_GUESSES_SINGLE_CHAR
+        } else {
+            MIN_GUESSES_MULTI_CHAR
+        };
+        m.guesses = m.guesses.max(min.log10());
+    }
+    matches
+}
+
+fn bruteforce_guesses(len: usize) -> f64 {
+    let guesses: f64 = (len as f64) * BRUTEFORCE_CARDINALITY.log10();
+    // a single random character has one more guess than a single character of a pattern, so
+    // patterns win ties
+    if len == 1 {
+        (MIN_GUESSES_SINGLE_CHAR + 1.0).log10()
+    } else {
+        guesses.max((MIN_GUESSES_MULTI_CHAR + 1.0).log10())
+    }
+}
+
+fn log10_factorial(n: usize) -> f64 {
+    (2..=n).map(|i| (i as f64).log10()).sum()
+}
+
+fn log10_binomial(n: usize, k: usize) -> f64 {
+    log10_factorial(n) - log10_factorial(k) - log10_factorial(n - k)
+}
+
+fn unleet(c: char, one: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '8' => 'b',
+        '(' | '{' | '[' | '<' => 'c',
+        '3' => 'e',
+        '6' | '9' => 'g',
+        '1' => one,
+        '!' | '|' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' | '+' => 't',
+        '%' => 'x',
+        '2' => 'z',
+        c => c,
+    }
+}
+
+/// Common passwords and words of the passphrase wordlist, also capitalized, reversed, or with
+/// l33t substitutions like p4ssw0rd
+fn dictionary_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let Dictionaries { common, words } = dictionaries();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let mut variants: Vec<Vec<char>> = vec![lower.clone()];
+    for one in ['i', 'l'] {
+        let unleeted: Vec<char> = lower
+            .iter()
+            .map(|c| unleet(*c, one))
+            .collect();
+        if !variants.contains(&unleeted) {
+            variants.push(unleeted);
+        }
+    }
+
+    let n: usize = chars.len();
+    for variant in &variants {
+        for start in 0..n {
+            for end in start + 1..=n.min(start + MAX_WORD_LEN) {
+                let original: &[char] = &chars[start..end];
+                let token: String = variant[start..end].iter().collect();
+                let reversed: String = token.chars().rev().collect();
+
+                let mut found: Option<(usize, Pattern, bool)> = None;
+                for (candidate, is_reversed) in [(&token, false), (&reversed, true)] {
+                    if is_reversed && end - start < 3 {
+                        continue;
+                    }
+                    let rank = common
+                        .get(candidate.as_str())
+                        .map(|r| (*r, Pattern::CommonPassword))
+                        .or_else(|| words.get(candidate.as_str()).map(|r| (*r, Pattern::Word)));
+                    if let Some((rank, pattern)) = rank {
+                        if found.is_none_or(|(best, _, _)| rank < best) {
+                            found = Some((rank, pattern, is_reversed));
+                        }
+                    }
+                }
+
+                if let Some((rank, pattern, is_reversed)) = found {
+                    let substitutions: usize = original
+                        .iter()
+                        .zip(&variant[start..end])
+                        .filter(|(o, v)| o.to_lowercase().next() != Some(**v))
+                        .count();
+                    let mut guesses: f64 = (rank as f64).log10();
+                    guesses += uppercase_variations(original);
+                    guesses += (substitutions as f64) * 2f64.log10();
+                    if is_reversed {
+                        guesses += 2f64.log10();
+                    }
+                    matches.push(Match { start, end, guesses, pattern });
+                }
+            }
+        }
+    }
+}
+
+/// log10 of the ways a word can be capitalized, given how many of its letters are uppercase
+fn uppercase_variations(word: &[char]) -> f64 {
+    let upper: usize = word
+        .iter()
+        .filter(|c| c.is_uppercase())
+        .count();
+    let lower: usize = word
+        .iter()
+        .filter(|c| c.is_lowercase())
+        .count();
+    if upper == 0 {
+        return 0.0;
+    }
+    let first_or_last: bool =
+        upper == 1 &&
+        (word.first().is_some_and(|c| c.is_uppercase()) ||
+            word.last().is_some_and(|c| c.is_uppercase()));
+    if lower == 0 || first_or_last {
+        return 2f64.log10();
+    }
+    let variations: f64 = (1..=upper.min(lower))
+        .map(|i| 10f64.powf(log10_binomial(upper + lower, i)))
+        .sum();
+    variations.log10()
+}
+
+fn char_class(c: char) -> u8 {
+    if c.is_ascii_lowercase() {
+        0
+    } else if c.is_ascii_uppercase() {
+        1
+    } else if c.is_ascii_digit() {
+        2
+    } else {
+        3
+    }
+}
+
+/// Runs like abc, 9876 or XYZ
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let n: usize = chars.len();
+    let mut start: usize = 0;
+    while start + 2 < n {
+        let delta: i64 = (chars[start + 1] as i64) - (chars[start] as i64);
+        let mut end: usize = start + 1;
+        if delta.abs() == 1 && char_class(chars[start]) < 3 {
+            while
+                end < n &&
+                (chars[end] as i64) - (chars[end - 1] as i64) == delta &&
+                char_class(chars[end]) == char_class(chars[start])
+            {
+                end += 1;
+            }
+        }
+        if end - start >= 3 {
+            let first: char = chars[start];
+            let base: f64 = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let mut guesses: f64 = (base * ((end - start) as f64)).log10();
+            if delta < 0 {
+                guesses += 2f64.log10();
+            }
+            matches.push(Match { start, end, guesses, pattern: Pattern::Sequence });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+/// A character or a group of characters repeated, like aaa or abcabc. Only the shortest group
+/// that repeats is used, abababab is abab twice but it's guessed as ab four times
+fn repeat_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let n: usize = chars.len();
+    for start in 0..n {
+        for base_len in 1..=(n - start) / 2 {
+            let base: &[char] = &chars[start..start + base_len];
+            let mut repeats: usize = 1;
+            while
+                start + (repeats + 1) * base_len <= n &&
+                &chars[start + repeats * base_len..start + (repeats + 1) * base_len] == base
+            {
+                repeats += 1;
+            }
+            if repeats < 2 || (base_len == 1 && repeats < 3) {
+                continue;
+            }
+            // a repeat that continues one starting earlier is already covered by it
+            if start >= base_len && &chars[start - base_len..start] == base {
+                break;
+            }
+            let base_guesses: f64 = if base_len == 1 {
+                match char_class(base[0]) {
+                    2 => 10f64.log10(),
+                    3 => 33f64.log10(),
+                    _ => 26f64.log10(),
+                }
+            } else {
+                most_guessable(base).0
+            };
+            matches.push(Match {
+                start,
+                end: start + repeats * base_len,
+                guesses: base_guesses + (repeats as f64).log10(),
+                pattern: Pattern::Repeat,
+            });
+            break;
+        }
+    }
+}
+
+/// Straight runs of keys on the same keyboard row, like qwerty or lkjh
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let n: usize = chars.len();
+    for row in KEYBOARD_ROWS {
+        let row: Vec<char> = row.chars().collect();
+        for start in 0..n {
+            let first: char = chars[start].to_ascii_lowercase();
+            let Some(position) = row.iter().position(|k| *k == first) else {
+                continue;
+            };
+            for direction in [1i64, -1] {
+                let mut end: usize = start + 1;
+                let mut key: i64 = position as i64;
+                while end < n {
+                    key += direction;
+                    if key < 0 || key >= (row.len() as i64) {
+                        break;
+                    }
+                    if chars[end].to_ascii_lowercase() != row[key as usize] {
+                        break;
+                    }
+                    end += 1;
+                }
+                if end - start >= 3 {
+                    let shifted: bool = chars[start..end].iter().any(|c| c.is_ascii_uppercase());
+                    let mut guesses: f64 = (KEYBOARD_STARTS * ((end - start) as f64)).log10();
+                    if shifted {
+                        guesses += 2f64.log10();
+                    }
+                    matches.push(Match { start, end, guesses, pattern: Pattern::Keyboard });
+                }
+            }
+        }
+    }
+}
+
+fn current_year() -> f64 {
+    1970.0 + ((unix_time() / 31_556_952) as f64)
+}
+
+fn year_space(year: u32) -> f64 {
+    ((year as f64) - current_year()).abs().max(MIN_YEAR_SPACE)
+}
+
+/// Years like 1987, and dates written only with digits, like 250387 or 19870325
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let n: usize = chars.len();
+    for start in 0..n {
+        for len in [4, 6, 8] {
+            let end: usize = start + len;
+            if end > n || !chars[start..end].iter().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            let digits: String = chars[start..end].iter().collect();
+            let number = |from: usize, to: usize| digits[from..to].parse::<u32>().unwrap_or(0);
+
+            let years: Vec<u32> = match len {
+                4 => vec![number(0, 4)].into_iter().filter(|y| (1900..2100).contains(y)).collect(),
+                _ => {
+                    let year_len: usize = len - 4;
+                    // day/month first, or year first
+                    let layouts: [(u32, u32, u32); 3] = [
+                        (number(0, 2), number(2, 4), number(4, len)),
+                        (number(2, 4), number(0, 2), number(4, len)),
+                        (number(year_len + 2, len), number(year_len, year_len + 2), number(0, year_len)),
+                    ];
+                    layouts
+                        .iter()
+                        .filter(|(day, month, _)| (1..=31).contains(day) && (1..=12).contains(month))
+                        .map(|(_, _, year)| {
+                            if year_len == 4 {
+                                *year
+                            } else if *year > 50 {
+                                1900 + year
+                            } else {
+                                2000 + year
+                            }
+                        })
+                        .filter(|y| (1900..2100).contains(y))
+                        .collect()
+                }
+            };
+
+            if let Some(space) = years.into_iter().map(year_space).reduce(f64::min) {
+                let guesses: f64 = if len == 4 { space } else { 365.0 * space };
+                matches.push(Match { start, end, guesses: guesses.log10(), pattern: Pattern::Date });
+            }
+        }
+    }
+}
+
+fn crack_seconds(log_guesses: f64) -> f64 {
+    10f64.powf(log_guesses) / GUESSES_PER_SECOND
+}
+
+fn display_time(seconds: f64) -> String {
+    let (amount, unit) = match seconds {
+        s if s < 1.0 => {
+            return String::from("less than a second");
+        }
+        s if s < MINUTE => (s, "second"),
+        s if s < HOUR => (s / MINUTE, "minute"),
+        s if s < DAY => (s / HOUR, "hour"),
+        s if s < MONTH => (s / DAY, "day"),
+        s if s < YEAR => (s / MONTH, "month"),
+        s if s < CENTURY => (s / YEAR, "year"),
+        _ => {
+            return String::from("centuries");
+        }
+    };
+    let amount: u64 = amount.round() as u64;
+    format!("{} {}{}", amount, unit, if amount == 1 { "" } else { "s" })
+}
+
+fn render(message: &str, input: &[char], masked: bool, feedback: bool) -> io::Result<()> {
+    let mut shown: String = if masked {
+        "*".repeat(input.len())
+    } else {
+        input.iter().collect()
+    };
+    let strength: String = if feedback && !input.is_empty() {
+        let mut password: String = input.iter().collect();
+        let strength: Strength = estimate(&password);
+        password.zeroize();
+        format!("  ({}, could be cracked in {})", strength.label(), strength.crack_time())
+    } else {
+        String::new()
+    };
+
+    let mut stdout = io::stdout();
+    queue!(
+        stdout,
+        cursor::MoveToColumn(0),
+        terminal::Clear(ClearType::CurrentLine),
+        Print(message),
+        Print(&shown),
+        Print(strength),
+        cursor::MoveToColumn((message.chars().count() + input.len()).min(u16::MAX as usize) as u16)
+    )?;
+    shown.zeroize();
+    stdout.flush()
+}
+
+/// Reads a password showing how strong it is, and how long it would take to crack it, while it's
+/// typed. masked hides the typed characters. Falls back to a regular prompt if there's no terminal
+pub fn prompt_with_strength(
+    message: &str,
+    initial: &str,
+    masked: bool
+) -> Result<String, InquireError> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return if masked {
+            Password::new(message).without_confirmation().prompt()
+        } else {
+            Text::new(message).with_initial_value(initial).prompt()
+        };
+    }
+
+    let mut input: Vec<char> = initial.chars().collect();
+    let result: Result<(), InquireError> = (|| {
+        let _raw: RawMode = RawMode::enable()?;
+        loop {
+            render(message, &input, masked, true)?;
+            if
+                let Event::Key(KeyEvent { code, modifiers, kind: KeyEventKind::Press, .. }) =
+                    event::read()?
+            {
+                match code {
+                    KeyCode::Enter => {
+                        render(message, &input, masked, false)?;
+                        return Ok(());
+                    }
+                    KeyCode::Esc => {
+                        return Err(InquireError::OperationCanceled);
+                    }
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Err(InquireError::OperationInterrupted);
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    })();
+    println!();
+
+    match result {
+        Ok(()) => {
+            let password: String = input.iter().collect();
+            input.zeroize();
+            Ok(password)
+        }
+        Err(e) => {
+            input.zeroize();
+            Err(e)
+        }
+    }
+}
+
+/// Checks a new master password against the minimum score in the config. Weak passwords are
+/// refused, or accepted only after confirming, depending on refuse_weak_master_password
+pub fn accept_master_password(password: &str, config: &Config) -> Result<bool, InquireError> {
+    let strength: Strength = estimate(password);
+    if strength.score >= config.min_master_password_score {
+        return Ok(true);
+    }
+
+    println!("This password is {}", strength);
+    if config.refuse_weak_master_password {
+        println!(
+            "Master passwords must be at least {}. Choose a stronger one",
+            score_label(config.min_master_password_score)
+        );
+        return Ok(false);
+    }
+    Confirm::new("Use it anyway?").with_default(false).prompt()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the score and the warning of each password
+    fn assert_weak(passwords: &[&str], warning: &str) {
+        for password in passwords {
+            let strength: Strength = estimate(password);
+            assert_eq!(strength.score, 0, "{} scored {}", password, strength.score);
+            assert_eq!(strength.warning, Some(warning), "wrong warning for {}", password);
+        }
+    }
+
+    #[test]
+    fn common_passwords_are_very_weak() {
+        assert_weak(&["password", "123456", "dragon", "monkey123"], "This is a very common password");
+    }
+
+    #[test]
+    fn l33t_spellings_are_found() {
+        assert_weak(&["p4ssw0rd", "P@ssw0rd"], "This is a very common password");
+        assert_weak(&["4bd0m3n", "Abdomen", "nemodba"], "A single word is easy to guess");
+    }
+
+    #[test]
+    fn repeats_are_very_weak() {
+        assert_weak(&["aaaaaaaaaa", "abcabcabcabc"], "Repeats like aaa or abcabc are easy to guess");
+    }
+
+    #[test]
+    fn sequences_are_very_weak() {
+        assert_weak(&["abcdefghij", "9876543210"], "Sequences like abc or 6543 are easy to guess");
+    }
+
+    #[test]
+    fn dates_are_very_weak() {
+        assert_weak(&["1990", "19071990", "199007"], "Dates and years are easy to guess");
+    }
+
+    #[test]
+    fn keyboard_rows_are_very_weak() {
+        assert_weak(&["zxcvbnm,./", "ghjkl;'"], "Rows of keys like qwerty are easy to guess");
+    }
+
+    #[test]
+    fn long_random_passwords_are_very_strong() {
+        for password in ["xK9#mQ2$vL7@pR4!", "correct horse battery staple"] {
+            let strength: Strength = estimate(password);
+            assert_eq!(strength.score, 4, "{} scored {}", password, strength.score);
+            assert_eq!(strength.warning, None);
+            assert_eq!(strength.crack_time(), "centuries");
+        }
+    }
+
+    #[test]
+    fn the_score_agrees_with_the_crack_time() {
+        // an l33t word with a few extra characters, which zxcvbn's thresholds call very strong
+        let strength: Strength = estimate("Tr0ub4dor&3");
+        assert_eq!(strength.score, 2);
+        assert_eq!(strength.to_string(), "fair, could be cracked in 4 months");
+
+        for password in ["j8Fq2LmZp0", "pelicanGargoyle", "19/07/1990", "Tr0ub4dor&3", "xK9#mQ2$vL7@pR4!"] {
+            let strength: Strength = estimate(password);
+            let seconds: f64 = crack_seconds(strength.log_guesses);
+            let score: usize = strength.score as usize;
+            assert!(score == 0 || seconds >= SCORE_CRACK_TIMES[score - 1], "{} scored too high", password);
+            assert!(score == 4 || seconds < SCORE_CRACK_TIMES[score], "{} scored too low", password);
+        }
+    }
+}
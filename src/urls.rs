@@ -0,0 +1,142 @@
+use publicsuffix::{ List, Psl };
+use std::sync::OnceLock;
+use url::Url;
+
+use crate::error::UrlError;
+
+/// The Public Suffix List (https://publicsuffix.org), which says under which suffixes anyone can
+/// register a domain (com, co.uk, github.io...). It's embedded in the executable, so matching
+/// never depends on external files or the network
+const PUBLIC_SUFFIX_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+fn public_suffixes() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| PUBLIC_SUFFIX_LIST.parse().expect("the bundled public suffix list is valid"))
+}
+
+/// How closely a URL matches one of an account's URLs, from the loosest to the strictest
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum UrlMatch {
+    /// Same registrable domain (eTLD+1): login.example.co.uk and www.example.co.uk
+    Domain,
+    /// Same host
+    Host,
+    /// The URL starts with the account's URL
+    Prefix,
+}
+
+/// Parses a URL, adding https:// if it has no scheme, so example.com/login works too. With a port,
+/// like localhost:8080, the host looks like a scheme, so https:// is also added when what was
+/// parsed has no host and the input has no ://
+pub fn parse_url(input: &str) -> Result<Url, UrlError> {
+    let input: &str = input.trim();
+    let url: Url = match Url::parse(input) {
+        Ok(url) if url.host_str().is_none() && !input.contains("://") => {
+            Url::parse(&format!("https://{}", input))?
+        }
+        Ok(url) => url,
+        Err(url::ParseError::RelativeUrlWithoutBase) => Url::parse(&format!("https://{}", input))?,
+        Err(e) => {
+            return Err(e.into());
+        }
+    };
+    if url.host_str().is_none_or(|host| host.is_empty()) {
+        return Err(UrlError::NoHost);
+    }
+    Ok(url)
+}
+
+/// The part of the host that someone registered: the public suffix plus one label. IP addresses
+/// and hosts that are a public suffix themselves are returned as they are
+pub fn registrable_domain(host: &str) -> String {
+    let host: String = host.trim_end_matches('.').to_ascii_lowercase();
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return host;
+    }
+    match public_suffixes().domain(host.as_bytes()) {
+        Some(domain) => String::from_utf8_lossy(domain.as_bytes()).into_owned(),
+        None => host,
+    }
+}
+
+/// How closely url matches account_url, None if they aren't related
+pub fn url_match(account_url: &str, url: &Url) -> Option<UrlMatch> {
+    let account_url: Url = parse_url(account_url).ok()?;
+    if url.as_str().starts_with(account_url.as_str()) {
+        return Some(UrlMatch::Prefix);
+    }
+    let (account_host, host) = (account_url.host_str()?, url.host_str()?);
+    if account_host == host {
+        return Some(UrlMatch::Host);
+    }
+    if registrable_domain(account_host) == registrable_domain(host) {
+        return Some(UrlMatch::Domain);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(account_url: &str, url: &str) -> Option<UrlMatch> {
+        url_match(account_url, &parse_url(url).unwrap())
+    }
+
+    #[test]
+    fn urls_without_a_scheme_get_https() {
+        assert_eq!(parse_url("example.com/login").unwrap().as_str(), "https://example.com/login");
+        assert_eq!(parse_url(" github.com ").unwrap().as_str(), "https://github.com/");
+        assert_eq!(parse_url("http://example.com").unwrap().scheme(), "http");
+    }
+
+    #[test]
+    fn urls_without_a_scheme_can_have_a_port() {
+        let url: Url = parse_url("localhost:8080").unwrap();
+        assert_eq!((url.host_str(), url.port()), (Some("localhost"), Some(8080)));
+        let url: Url = parse_url("example.com:8443/login").unwrap();
+        assert_eq!(url.as_str(), "https://example.com:8443/login");
+        let url: Url = parse_url("192.168.1.1:8080").unwrap();
+        assert_eq!((url.host_str(), url.port()), (Some("192.168.1.1"), Some(8080)));
+    }
+
+    #[test]
+    fn urls_without_a_host_are_rejected() {
+        assert!(matches!(parse_url("file:///etc/passwd"), Err(UrlError::NoHost)));
+        assert!(parse_url("https://").is_err());
+        assert!(parse_url("").is_err());
+    }
+
+    #[test]
+    fn prefix_host_and_domain_matches() {
+        assert_eq!(matches("example.com/login", "https://example.com/login?next=/"), Some(UrlMatch::Prefix));
+        assert_eq!(matches("example.com/login", "https://example.com/signup"), Some(UrlMatch::Host));
+        assert_eq!(matches("login.example.co.uk", "www.example.co.uk"), Some(UrlMatch::Domain));
+        assert_eq!(matches("example.com", "example.org"), None);
+        // co.uk is a public suffix, so these are unrelated
+        assert_eq!(matches("one.co.uk", "two.co.uk"), None);
+    }
+
+    #[test]
+    fn private_suffixes_separate_their_sites() {
+        assert_eq!(registrable_domain("docs.alice.github.io"), "alice.github.io");
+        assert_eq!(matches("alice.github.io", "bob.github.io"), None);
+        assert_eq!(matches("alice.github.io", "docs.alice.github.io"), Some(UrlMatch::Domain));
+    }
+
+    #[test]
+    fn ip_addresses_only_match_themselves() {
+        assert_eq!(registrable_domain("192.168.1.1"), "192.168.1.1");
+        assert_eq!(matches("192.168.1.1", "https://192.168.1.1/admin"), Some(UrlMatch::Prefix));
+        assert_eq!(matches("192.168.1.1", "192.168.1.2"), None);
+        assert_eq!(matches("[::1]:8080", "http://[::1]/"), Some(UrlMatch::Host));
+    }
+
+    #[test]
+    fn ports_are_part_of_the_prefix() {
+        assert_eq!(matches("localhost:8080", "localhost:8080/app"), Some(UrlMatch::Prefix));
+        // the host is the same, whatever the port
+        assert_eq!(matches("localhost:8080", "localhost:3000"), Some(UrlMatch::Host));
+        assert_eq!(matches("example.com:8443/login", "https://example.com:8443/login"), Some(UrlMatch::Prefix));
+    }
+}
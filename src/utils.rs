@@ -1,4 +1,10 @@
+use crossterm::terminal;
+use std::fs::{ self, File, OpenOptions };
+use std::io::{ self, Write };
+use std::path::{ Path, PathBuf };
 use std::process::Command;
+use std::time::{ SystemTime, UNIX_EPOCH };
+use uuid::Uuid;
 
 pub fn clear_terminal_screen() {
     if cfg!(target_os = "windows") {
@@ -16,3 +22,81 @@ pub fn clear_terminal_screen() {
             .expect("failed to wait");
     };
 }
+
+pub fn wait_for_enter() {
+    print!("Press Enter to continue...");
+    _ = io::stdout().flush();
+    _ = io::stdin().read_line(&mut String::new());
+}
+
+/// Puts the terminal in raw mode, to read single key presses, and leaves it when dropped, even if
+/// reading the input fails
+pub struct RawMode;
+
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Seconds since the Unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Writes contents to path without ever leaving a half written file behind: the data is written
+/// to a temporary file in the same directory, flushed to disk, and then renamed over path.
+/// On Unix, the file is only readable and writable by its owner (0600).
+/// If path is a symlink, the file it points to is replaced instead, so a file linked from a synced
+/// folder stays a link. A dangling symlink is replaced by a regular file
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> io::Result<()> {
+    let resolved: PathBuf = fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf());
+    let path: &Path = &resolved;
+    let dir: &Path = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let file_name: String = match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+    };
+    let temp_path = dir.join(format!(".{}.{}.tmp", file_name, Uuid::new_v4()));
+
+    let written: io::Result<()> = write_temp_file(&temp_path, contents);
+    if let Err(e) = written.and_then(|_| fs::rename(&temp_path, path)) {
+        _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    // the rename itself is only durable once the directory is flushed too
+    #[cfg(unix)]
+    if let Ok(d) = File::open(dir) {
+        _ = d.sync_all();
+    }
+    Ok(())
+}
+
+fn write_temp_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options: OpenOptions = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file: File = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}